@@ -0,0 +1,37 @@
+from .gilknocker import (
+    ContentionAssertion,
+    KnockDecorator,
+    KnockedFunction,
+    KnockKnock,
+    KnockKnockBuilder,
+    Measurement,
+    MergeableStats,
+    SampleStream,
+    SampleWaiter,
+    Snapshot,
+    StopWaiter,
+    __version__,
+    default,
+    knock,
+    start_default,
+    stop_default,
+)
+
+__all__ = [
+    "ContentionAssertion",
+    "KnockDecorator",
+    "KnockedFunction",
+    "KnockKnock",
+    "KnockKnockBuilder",
+    "Measurement",
+    "MergeableStats",
+    "SampleStream",
+    "SampleWaiter",
+    "Snapshot",
+    "StopWaiter",
+    "__version__",
+    "default",
+    "knock",
+    "start_default",
+    "stop_default",
+]
@@ -0,0 +1,55 @@
+"""
+pytest plugin providing a ``gil_contention`` fixture, and a ``--gil-threshold``
+option failing tests using it whose GIL contention exceeded the threshold.
+"""
+import pytest
+
+from gilknocker import KnockKnock
+
+
+def pytest_addoption(parser):
+    group = parser.getgroup("gilknocker")
+    group.addoption(
+        "--gil-threshold",
+        type=float,
+        default=None,
+        help="Fail tests using the gil_contention fixture whose GIL contention "
+        "exceeded this threshold, between 0 and 1.",
+    )
+
+
+@pytest.fixture(scope="session")
+def _gilknocker_session():
+    knocker = KnockKnock(polling_interval_micros=1_000, sleeping_interval_micros=10_000)
+    knocker.start()
+    yield knocker
+    knocker.stop()
+
+
+@pytest.fixture
+def gil_contention(_gilknocker_session):
+    """
+    A running ``KnockKnock``, with its contention metric reset for each test.
+    """
+    _gilknocker_session.reset_contention_metric()
+    yield _gilknocker_session
+
+
+@pytest.hookimpl(hookwrapper=True)
+def pytest_runtest_makereport(item, call):
+    """
+    Fail a passing test using ``gil_contention`` whose GIL contention, going by the
+    peak and final contention metric, exceeded ``--gil-threshold``.
+    """
+    outcome = yield
+    report = outcome.get_result()
+    threshold = item.config.getoption("gil_threshold")
+    knocker = getattr(item, "funcargs", {}).get("gil_contention")
+    if threshold is None or knocker is None or call.when != "call" or not report.passed:
+        return
+    metric = max(knocker.peak_contention_metric, knocker.contention_metric)
+    if metric > threshold:
+        report.outcome = "failed"
+        report.longrepr = "GIL contention of {:.4f} exceeded threshold of {}".format(
+            metric, threshold
+        )
@@ -1,34 +1,1131 @@
 #[deny(missing_docs)]
-use parking_lot::{const_rwlock, RwLock};
+use parking_lot::{Condvar, Mutex, RwLock};
 use pyo3::ffi::{PyEval_InitThreads, PyEval_ThreadsInitialized};
 use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
 use pyo3::PyResult;
+use serde::{Deserialize, Serialize};
 use std::ops::DerefMut;
+use std::panic::AssertUnwindSafe;
 use std::{
+    any::Any,
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
     mem::take,
+    path::PathBuf,
     sync::{
-        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, Sender, TryRecvError},
         Arc,
     },
     thread,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 #[pymodule]
 fn gilknocker(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     m.add_class::<KnockKnock>()?;
+    m.add_class::<Snapshot>()?;
+    m.add_class::<MergeableStats>()?;
+    m.add_class::<KnockKnockBuilder>()?;
+    m.add_class::<ContentionAssertion>()?;
+    m.add_class::<Measurement>()?;
+    m.add_class::<StopWaiter>()?;
+    m.add_class::<SampleStream>()?;
+    m.add_class::<SampleWaiter>()?;
+    m.add_class::<KnockDecorator>()?;
+    m.add_class::<KnockedFunction>()?;
+    m.add_function(wrap_pyfunction!(knock, m)?)?;
+    m.add_function(wrap_pyfunction!(default_knocker, m)?)?;
+    m.add_function(wrap_pyfunction!(start_default, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_default, m)?)?;
     Ok(())
 }
 
 /// Possible messages to pass to the monitoring thread.
 enum Message {
     Stop,
-    Reset,
+    /// Reset the metrics, discarding any partial sampling window, and acknowledge
+    /// with the given generation.
+    Reset(u64),
+    SetPolling(Duration),
+    SetSampling(Duration),
+    SetSleeping(Duration),
+    /// Adapt the polling interval within ``(min, max)`` to contention, or stop adapting.
+    SetAdaptive(Option<(Duration, Duration)>),
+    /// Stop sampling, only waiting on messages, until resumed.
+    Pause,
+    Resume,
+    /// Panic, to test handling of panics in the monitoring thread.
+    Panic,
 }
 
-/// Acknowledgement from monitoring thread
-struct Ack;
+/// Acknowledgement from monitoring thread of a reset to the given generation, with the
+/// contention metric just before resetting it.
+struct Ack(u64, f64);
+
+/// Signals the monitoring thread has exited, so stopping can wake up as soon as it does.
+#[derive(Default)]
+struct ExitSignal {
+    exited: Mutex<bool>,
+    condvar: Condvar,
+}
+
+impl ExitSignal {
+    fn notify(&self) {
+        *self.exited.lock() = true;
+        self.condvar.notify_all();
+    }
+
+    /// Has the thread exited?
+    fn has_exited(&self) -> bool {
+        *self.exited.lock()
+    }
+
+    /// Wait up to ``timeout`` for the thread to exit, returning whether it did.
+    fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut exited = self.exited.lock();
+        while !*exited {
+            if self.condvar.wait_until(&mut exited, deadline).timed_out() {
+                break;
+            }
+        }
+        *exited
+    }
+}
+
+/// Notifies the ``ExitSignal`` when dropped at the end of the monitoring thread,
+/// including when it panics.
+struct NotifyOnExit(Arc<ExitSignal>);
+
+impl Drop for NotifyOnExit {
+    fn drop(&mut self) {
+        self.0.notify();
+    }
+}
+
+/// OS thread id of the calling thread, as in ``threading.get_native_id()``, where the
+/// platform supports it.
+#[cfg(target_os = "linux")]
+fn native_thread_id() -> Option<u64> {
+    // SAFETY: gettid takes no arguments and can't fail
+    Some(unsafe { libc::syscall(libc::SYS_gettid) } as u64)
+}
+
+#[cfg(target_os = "macos")]
+fn native_thread_id() -> Option<u64> {
+    let mut id = 0;
+    // SAFETY: a null thread is the calling thread, and ``id`` is valid to write to
+    match unsafe { libc::pthread_threadid_np(0, &mut id) } {
+        0 => Some(id),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn native_thread_id() -> Option<u64> {
+    None
+}
+
+/// Round to ``digits`` decimal places, with ties to even like Python's ``round()``.
+/// Formatting rounds the exact binary value, where scaling by a power of 10 first
+/// could itself introduce rounding errors.
+fn round_half_even(value: f64, digits: u32) -> f64 {
+    format!("{:.*}", digits as usize, value)
+        .parse()
+        .unwrap_or(value)
+}
+
+/// Ratio of time spent waiting for the GIL to time spent sampling, clamped to [0, 1]
+/// as each poll's wait may slightly exceed the accounted sampling time. No sampling
+/// time gives zero contention, rather than NaN.
+fn contention_ratio(time_waiting: Duration, time_sampling: Duration) -> f64 {
+    if time_sampling.as_micros() == 0 {
+        return 0_f64;
+    }
+    (time_waiting.as_micros() as f64 / time_sampling.as_micros() as f64).clamp(0.0, 1.0)
+}
+
+/// Message carried by a thread's panic payload, for reporting it back to Python.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Number of equal-width bins over [0, 1] that per-sample contention ratios are counted in,
+/// which ``KnockKnock.histogram()`` then combines into the requested number of buckets.
+const HISTOGRAM_BINS: usize = 1000;
+
+/// Sampling cycles, of sampling then sleeping, without any sample completing after which
+/// the monitoring thread is considered unhealthy, see ``KnockKnock.healthy``.
+const HEALTHY_CYCLES: u32 = 3;
+
+/// Number of the most recent GIL acquisition latencies kept for
+/// ``KnockKnock.latency_percentiles()``.
+const LATENCY_RESERVOIR_SIZE: usize = 4096;
+
+/// Polling intervals below this are busy-waited, rather than slept, when spinning,
+/// see ``KnockKnock.spin``.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+
+/// Wait ``interval`` between polls of the GIL. When ``spin`` is set and the interval is
+/// below ``SPIN_THRESHOLD``, busy-wait for it instead of sleeping, as the OS may sleep
+/// far longer than such short intervals.
+fn wait_between_polls(interval: Duration, spin: bool) {
+    if spin && interval < SPIN_THRESHOLD {
+        let deadline = Instant::now() + interval;
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    } else {
+        thread::sleep(interval);
+    }
+}
+
+/// Polling interval to use after a sampling window with contention ``ratio``. When adaptive,
+/// it shrinks linearly from ``max`` with no contention to ``min`` with full contention:
+/// ``max - ratio * (max - min)``, otherwise it's the configured ``polling_interval``.
+fn adaptive_polling_interval(
+    polling_interval: Duration,
+    adaptive: Option<(Duration, Duration)>,
+    ratio: f64,
+) -> Duration {
+    match adaptive {
+        Some((min, max)) => max - (max - min).mul_f64(ratio.clamp(0.0, 1.0)),
+        None => polling_interval,
+    }
+}
+
+/// Configuration of a knocker when pickled: ``(polling_interval_micros, sampling_interval_micros,
+/// sleeping_interval_micros, timeout_micros, ema_alpha, window_secs, history_size)``
+type PickleState = (u64, u64, u64, u64, Option<f64>, Option<f64>, usize);
+
+/// Raise ``ValueError`` for a zero interval, which would make the monitoring thread busy-spin.
+fn validate_interval(name: &str, interval: Duration) -> PyResult<()> {
+    if interval.is_zero() {
+        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "{} must be greater than 0",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Warn when ``timeout`` is shorter than ``polling_interval``. The monitoring thread wakes
+/// for messages at once while sleeping between sampling windows, but only checks for them
+/// between polls while sampling, so resets and stops would often time out.
+fn warn_short_timeout(py: Python, timeout: Duration, polling_interval: Duration) -> PyResult<()> {
+    if timeout < polling_interval {
+        let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+        PyErr::warn(
+            py,
+            warning,
+            &format!(
+                "timeout_micros ({}) is less than polling_interval_micros ({}), so resets and \
+                 stops may time out while the monitoring thread waits between polls; use a \
+                 timeout_micros of at least polling_interval_micros.",
+                timeout.as_micros(),
+                polling_interval.as_micros()
+            ),
+            0,
+        )?;
+    }
+    Ok(())
+}
+
+/// Weak set of knockers started in this process, reset in the child after ``fork()``.
+static FORK_REGISTRY: pyo3::sync::GILOnceCell<PyObject> = pyo3::sync::GILOnceCell::new();
+
+/// The ``FORK_REGISTRY``, registering a single ``os.register_at_fork`` hook for all
+/// knockers on first use, as hooks can't be unregistered.
+fn fork_registry(py: Python) -> PyResult<&PyAny> {
+    if let Some(registry) = FORK_REGISTRY.get(py) {
+        return Ok(registry.as_ref(py));
+    }
+    let registry = py.import("weakref")?.getattr("WeakSet")?.call0()?;
+    let os = py.import("os")?;
+    if os.hasattr("register_at_fork")? {
+        let locals = pyo3::types::PyDict::new(py);
+        locals.set_item("knockers", registry)?;
+        locals.set_item("os", os)?;
+        py.run(
+            "os.register_at_fork(after_in_child=lambda: [k._after_fork_in_child() for k in list(knockers)])",
+            None,
+            Some(locals),
+        )?;
+    }
+    let _ = FORK_REGISTRY.set(py, registry.into());
+    Ok(FORK_REGISTRY
+        .get(py)
+        .expect("fork registry was just set")
+        .as_ref(py))
+}
+
+/// Weak set of knockers started in this process, stopped when the interpreter exits.
+static EXIT_REGISTRY: pyo3::sync::GILOnceCell<PyObject> = pyo3::sync::GILOnceCell::new();
+
+/// The ``EXIT_REGISTRY``, registering a single ``atexit`` hook for all knockers on first
+/// use, rather than one per ``start()`` which would never be unregistered.
+fn exit_registry(py: Python) -> PyResult<&PyAny> {
+    if let Some(registry) = EXIT_REGISTRY.get(py) {
+        return Ok(registry.as_ref(py));
+    }
+    let registry = py.import("weakref")?.getattr("WeakSet")?.call0()?;
+    let locals = pyo3::types::PyDict::new(py);
+    locals.set_item("knockers", registry)?;
+    locals.set_item("atexit", py.import("atexit")?)?;
+    py.run(
+        "atexit.register(lambda: [k.stop() for k in list(knockers)])",
+        None,
+        Some(locals),
+    )?;
+    let _ = EXIT_REGISTRY.set(py, registry.into());
+    Ok(EXIT_REGISTRY
+        .get(py)
+        .expect("exit registry was just set")
+        .as_ref(py))
+}
+
+/// Lock ``mutex`` in the child after ``fork()``. A thread of the parent holding it at the
+/// time doesn't exist in the child, so would never release it.
+fn lock_after_fork<T>(mutex: &Mutex<T>) -> parking_lot::MutexGuard<'_, T> {
+    if mutex.is_locked() {
+        // Only the forking thread exists in the child, which didn't hold it
+        unsafe { mutex.force_unlock() };
+    }
+    mutex.lock()
+}
+
+/// Current contention metric and configuration of a knocker.
+#[derive(Serialize)]
+struct State {
+    contention_metric: f64,
+    polling_interval_micros: u64,
+    sampling_interval_micros: u64,
+    sleeping_interval_micros: u64,
+    timeout_micros: u64,
+    is_running: bool,
+}
+
+/// Archive of a knocker's configuration and aggregate metrics, optionally with its
+/// retained sample history, see ``KnockKnock.dump_json()``.
+#[derive(Serialize)]
+struct Dump {
+    #[serde(flatten)]
+    state: State,
+    peak_contention_metric: f64,
+    num_samples: u64,
+    total_time_waiting_micros: u64,
+    total_time_sampling_micros: u64,
+    elapsed_secs: f64,
+    history_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history: Option<Vec<DumpedSample>>,
+}
+
+/// Retained sample in a ``Dump``.
+#[derive(Serialize)]
+struct DumpedSample {
+    /// Seconds since ``start()`` or the last reset.
+    t: f64,
+    ratio: f64,
+    time_waiting_micros: u64,
+    time_sampling_micros: u64,
+}
+
+/// Metrics shared between the knocker and its monitoring thread.
+#[derive(Default)]
+struct Stats {
+    /// Bit pattern of the ``f64`` contention metric, so reading it never blocks.
+    contention_metric: AtomicU64,
+    /// Bit pattern of the ``f64`` contention ratio of only the last completed sample.
+    instant_contention_metric: AtomicU64,
+    peak_contention_metric: RwLock<f64>,
+    totals: RwLock<(Duration, Duration)>,
+    /// Time spent sleeping between sampling windows.
+    time_sleeping: RwLock<Duration>,
+    num_samples: AtomicU64,
+    /// Polling interval currently used by the monitoring thread, in microseconds.
+    effective_polling_micros: AtomicU64,
+    /// GIL acquisitions in the last sample, and in total over all samples.
+    last_polls: AtomicU64,
+    total_polls: AtomicU64,
+    /// Samples whose contention ratio exceeded ``count_threshold``, if set.
+    samples_over_threshold: AtomicU64,
+    count_threshold: RwLock<Option<f64>>,
+    /// Count of samples per contention ratio bin, see ``HISTOGRAM_BINS``.
+    histogram: Mutex<Vec<u64>>,
+    ema_alpha: Option<f64>,
+    /// Exponential moving averages of ``(time_waiting, time_sampling)`` in microseconds,
+    /// when ``ema_alpha`` is set.
+    ema_durations: Mutex<(f64, f64)>,
+    window: Option<Duration>,
+    /// Completed samples of ``(completed_at, time_waiting, time_sampling)`` within ``window``
+    window_samples: Mutex<VecDeque<(Instant, Duration, Duration)>>,
+    /// Most recent per-sample ``(timestamp, contention ratio, time_waiting, time_sampling)``,
+    /// oldest first, bounded by ``history_size``. Timestamps are seconds since ``origin``.
+    history: Mutex<VecDeque<(f64, f64, Duration, Duration)>>,
+    history_size: usize,
+    /// Start of the timeline, set when starting and on reset.
+    origin: RwLock<Option<Instant>>,
+    /// Sampling windows which took longer than sampling and sleeping together should.
+    overrun_count: AtomicU64,
+    /// Time taken to acquire the GIL on each of the most recent polls, oldest first,
+    /// bounded by ``LATENCY_RESERVOIR_SIZE``.
+    latencies: Mutex<VecDeque<Duration>>,
+    /// Set while a sampling window is open, cleared while sleeping, paused or exited.
+    sampling: AtomicBool,
+    /// Set by ``stop()`` and on drop, possibly as the interpreter finalizes, after which the
+    /// monitoring thread no longer acquires the GIL, to poll or call back.
+    shutting_down: AtomicBool,
+    /// Message of a panic in the monitoring thread, if any.
+    error: Mutex<Option<String>>,
+    /// Samples completed over the lifetime of the monitoring thread, unaffected by
+    /// resets, with ``sampled`` notified on each one.
+    samples_completed: Mutex<u64>,
+    sampled: Condvar,
+}
+
+impl Stats {
+    /// Add a completed sample of ``(time_waiting, time_sampling)``, taken with ``polls``
+    /// acquisitions of the GIL, to the running totals and recalculate the contention
+    /// metric from them.
+    /// All metrics are updated while holding the ``totals`` lock, for consistent snapshots.
+    fn accumulate(&self, sample: (Duration, Duration), polls: u64) {
+        let mut totals = self.totals.write();
+        self.num_samples.fetch_add(1, Ordering::Relaxed);
+        self.last_polls.store(polls, Ordering::Relaxed);
+        self.total_polls.fetch_add(polls, Ordering::Relaxed);
+        let ratio = contention_ratio(sample.0, sample.1);
+        self.instant_contention_metric
+            .store(ratio.to_bits(), Ordering::Relaxed);
+        if matches!(*self.count_threshold.read(), Some(threshold) if ratio > threshold) {
+            self.samples_over_threshold.fetch_add(1, Ordering::Relaxed);
+        }
+        {
+            let mut histogram = self.histogram.lock();
+            histogram.resize(HISTOGRAM_BINS, 0);
+            // A ratio of exactly 1 goes in the last bin
+            let bin = ((ratio * HISTOGRAM_BINS as f64) as usize).min(HISTOGRAM_BINS - 1);
+            histogram[bin] += 1;
+        }
+        if self.history_size > 0 {
+            let mut history = self.history.lock();
+            if history.len() == self.history_size {
+                history.pop_front();
+            }
+            let timestamp = self
+                .origin
+                .read()
+                .map(|origin| origin.elapsed().as_secs_f64())
+                .unwrap_or_default();
+            history.push_back((timestamp, ratio, sample.0, sample.1));
+        }
+        totals.0 += sample.0;
+        totals.1 += sample.1;
+        let metric = match (self.ema_alpha, self.window) {
+            (Some(alpha), _) => {
+                // Average the durations rather than the ratio, so each sample's ratio is
+                // weighted by how long it actually sampled for.
+                let mut ema = self.ema_durations.lock();
+                ema.0 = alpha * sample.0.as_micros() as f64 + (1.0 - alpha) * ema.0;
+                ema.1 = alpha * sample.1.as_micros() as f64 + (1.0 - alpha) * ema.1;
+                if ema.1 == 0.0 {
+                    0_f64
+                } else {
+                    (ema.0 / ema.1).clamp(0.0, 1.0)
+                }
+            }
+            (None, Some(window)) => {
+                let mut window_samples = self.window_samples.lock();
+                let now = Instant::now();
+                window_samples.push_back((now, sample.0, sample.1));
+                while let Some((completed_at, ..)) = window_samples.front() {
+                    if now.duration_since(*completed_at) <= window {
+                        break;
+                    }
+                    window_samples.pop_front();
+                }
+                let (waiting, sampling) = window_samples
+                    .iter()
+                    .fold((Duration::ZERO, Duration::ZERO), |acc, (_, w, s)| {
+                        (acc.0 + *w, acc.1 + *s)
+                    });
+                contention_ratio(waiting, sampling)
+            }
+            (None, None) => contention_ratio(totals.0, totals.1),
+        };
+        self.contention_metric
+            .store(metric.to_bits(), Ordering::Relaxed);
+        let mut peak = self.peak_contention_metric.write();
+        *peak = peak.max(metric);
+        drop(peak);
+        drop(totals);
+        *self.samples_completed.lock() += 1;
+        self.sampled.notify_all();
+    }
+
+    /// Wait up to ``timeout`` for the next sample to complete, returning whether one did.
+    fn wait_for_sample(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut completed = self.samples_completed.lock();
+        let seen = *completed;
+        while *completed == seen {
+            if self
+                .sampled
+                .wait_until(&mut completed, deadline)
+                .timed_out()
+            {
+                break;
+            }
+        }
+        *completed != seen
+    }
+
+    /// Record the time taken to acquire the GIL on a single poll.
+    fn record_latency(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock();
+        if latencies.len() == LATENCY_RESERVOIR_SIZE {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// Current contention metric.
+    fn contention_metric(&self) -> f64 {
+        f64::from_bits(self.contention_metric.load(Ordering::Relaxed))
+    }
+
+    /// Contention ratio of the last completed sample.
+    fn instant_contention_metric(&self) -> f64 {
+        f64::from_bits(self.instant_contention_metric.load(Ordering::Relaxed))
+    }
+
+    /// Consistent ``(contention_metric, peak_contention_metric, num_samples, totals)``
+    fn snapshot(&self) -> (f64, f64, u64, (Duration, Duration)) {
+        let totals = self.totals.read();
+        (
+            self.contention_metric(),
+            *self.peak_contention_metric.read(),
+            self.num_samples.load(Ordering::Relaxed),
+            *totals,
+        )
+    }
+
+    /// Zero all metrics.
+    fn reset(&self) {
+        let mut totals = self.totals.write();
+        *totals = Default::default();
+        self.contention_metric
+            .store(0_f64.to_bits(), Ordering::Relaxed);
+        self.instant_contention_metric
+            .store(0_f64.to_bits(), Ordering::Relaxed);
+        *self.peak_contention_metric.write() = 0_f64;
+        self.num_samples.store(0, Ordering::Relaxed);
+        self.last_polls.store(0, Ordering::Relaxed);
+        self.total_polls.store(0, Ordering::Relaxed);
+        self.samples_over_threshold.store(0, Ordering::Relaxed);
+        self.overrun_count.store(0, Ordering::Relaxed);
+        *self.ema_durations.lock() = (0.0, 0.0);
+        self.window_samples.lock().clear();
+        self.history.lock().clear();
+        self.histogram.lock().clear();
+        self.latencies.lock().clear();
+        *self.time_sleeping.write() = Duration::ZERO;
+        *self.origin.write() = Some(Instant::now());
+    }
+
+    /// Record a panic from the monitoring thread, keeping the first one.
+    fn set_error(&self, payload: Box<dyn Any + Send>) {
+        self.error
+            .lock()
+            .get_or_insert_with(|| panic_message(payload));
+    }
+}
+
+/// Callback fired when the contention metric crosses a threshold.
+struct ThresholdCallback {
+    threshold: f64,
+    on_rise: PyObject,
+    on_fall: Option<PyObject>,
+    above: bool,
+}
+
+/// Callbacks fired when the contention metric enters an alerting state above ``high``,
+/// and exits it only once below ``low``.
+struct HysteresisCallbacks {
+    high: f64,
+    low: f64,
+    on_enter: PyObject,
+    on_exit: PyObject,
+    alerting: bool,
+}
+
+/// Periodic logging of the contention metric via Python's ``logging`` module.
+struct Logging {
+    every: Duration,
+    logger_name: String,
+    last: Instant,
+}
+
+/// Callback fired with the contention metric every ``every``, independent of sampling.
+struct Heartbeat {
+    every: Duration,
+    callback: PyObject,
+    next: Instant,
+}
+
+/// Streaming of a JSON Lines record to a raw file descriptor every ``every`` samples.
+struct Stream {
+    fd: i32,
+    every: usize,
+    /// Samples completed since the last record was written.
+    pending: usize,
+}
+
+/// Record written per streamed sample, see ``KnockKnock.stream_to_fd()``.
+#[derive(Serialize)]
+struct StreamRecord {
+    /// Seconds since the Unix epoch.
+    t: f64,
+    ratio: f64,
+    metric: f64,
+}
+
+/// Bounded queue of ``(timestamp, metric)`` samples the monitoring thread pushes to for a
+/// ``SampleStream``. When full the oldest sample is dropped, so it never blocks the thread.
+struct SampleQueue {
+    /// Queued samples, and whether the queue is closed.
+    state: Mutex<(VecDeque<(f64, f64)>, bool)>,
+    available: Condvar,
+    capacity: usize,
+}
+
+impl SampleQueue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new((VecDeque::with_capacity(capacity), false)),
+            available: Condvar::new(),
+            capacity,
+        }
+    }
+
+    fn push(&self, sample: (f64, f64)) {
+        let mut state = self.state.lock();
+        if state.1 {
+            return;
+        }
+        if state.0.len() == self.capacity {
+            state.0.pop_front();
+        }
+        state.0.push_back(sample);
+        self.available.notify_all();
+    }
+
+    /// Close the queue, discarding any queued samples if ``discard``.
+    fn close(&self, discard: bool) {
+        let mut state = self.state.lock();
+        state.1 = true;
+        if discard {
+            state.0.clear();
+        }
+        self.available.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().1
+    }
+
+    /// Wait for the next sample, ``None`` once the queue is closed and drained.
+    fn next(&self) -> Option<(f64, f64)> {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(sample) = state.0.pop_front() {
+                return Some(sample);
+            }
+            if state.1 {
+                return None;
+            }
+            self.available.wait(&mut state);
+        }
+    }
+}
+
+/// Interrupts the main thread once the contention metric has stayed above ``threshold``
+/// for ``duration``.
+struct Watchdog {
+    threshold: f64,
+    duration: Duration,
+    above_since: Option<Instant>,
+    tripped: bool,
+}
+
+/// User callbacks invoked by the monitoring thread, kept across restarts.
+#[derive(Default)]
+struct Callbacks {
+    threshold: Option<ThresholdCallback>,
+    sample: Option<PyObject>,
+    hysteresis: Option<HysteresisCallbacks>,
+    logging: Option<Logging>,
+    watchdog: Option<Watchdog>,
+    otel: Option<PyObject>,
+    /// Called with the overrun in microseconds when a sampling window overruns.
+    overrun: Option<PyObject>,
+    stream: Option<Stream>,
+    heartbeat: Option<Heartbeat>,
+    /// Queues of open ``SampleStream``s, closed once the monitoring thread exits.
+    sample_queues: Vec<Arc<SampleQueue>>,
+    /// Exit signal of the monitoring thread pushing to ``sample_queues``, if one is
+    /// running, which closes them on exiting.
+    stream_source: Option<Arc<ExitSignal>>,
+    /// Minimum time between firing callbacks, however often samples complete.
+    min_interval: Duration,
+}
+
+impl Callbacks {
+    /// Callbacks to invoke with a newly calculated contention metric, updating
+    /// any edge-triggered state.
+    fn triggered(&mut self, metric: f64) -> Vec<PyObject> {
+        let mut triggered = vec![];
+        if let Some(threshold) = self.threshold.as_mut() {
+            let above = metric > threshold.threshold;
+            if above && !threshold.above {
+                triggered.push(threshold.on_rise.clone());
+            } else if !above && threshold.above {
+                triggered.extend(threshold.on_fall.clone());
+            }
+            threshold.above = above;
+        }
+        if let Some(hysteresis) = self.hysteresis.as_mut() {
+            if !hysteresis.alerting && metric > hysteresis.high {
+                hysteresis.alerting = true;
+                triggered.push(hysteresis.on_enter.clone());
+            } else if hysteresis.alerting && metric < hysteresis.low {
+                hysteresis.alerting = false;
+                triggered.push(hysteresis.on_exit.clone());
+            }
+        }
+        triggered
+    }
+
+    /// Name of the logger to log the contention metric to, if due.
+    fn log_due(&mut self) -> Option<String> {
+        let logging = self.logging.as_mut()?;
+        if logging.last.elapsed() < logging.every {
+            return None;
+        }
+        logging.last = Instant::now();
+        Some(logging.logger_name.clone())
+    }
+
+    /// When the heartbeat is next due, if set.
+    fn heartbeat_at(&self) -> Option<Instant> {
+        self.heartbeat.as_ref().map(|heartbeat| heartbeat.next)
+    }
+
+    /// Heartbeat callback to invoke, if due, scheduling the next one. Missed heartbeats,
+    /// e.g. while the GIL was held, aren't caught up on.
+    fn heartbeat_due(&mut self) -> Option<PyObject> {
+        let heartbeat = self.heartbeat.as_mut()?;
+        let now = Instant::now();
+        if now < heartbeat.next {
+            return None;
+        }
+        heartbeat.next += heartbeat.every;
+        if heartbeat.next <= now {
+            heartbeat.next = now + heartbeat.every;
+        }
+        Some(heartbeat.callback.clone())
+    }
+
+    /// File descriptor to stream the latest sample to, if due.
+    fn stream_due(&mut self) -> Option<i32> {
+        let stream = self.stream.as_mut()?;
+        stream.pending += 1;
+        if stream.pending < stream.every {
+            return None;
+        }
+        stream.pending = 0;
+        Some(stream.fd)
+    }
+
+    /// Whether the watchdog has just tripped, with the contention metric having stayed
+    /// above its threshold for its duration. It trips at most once until reset.
+    fn watchdog_tripped(&mut self, metric: f64) -> bool {
+        let Some(watchdog) = self.watchdog.as_mut() else {
+            return false;
+        };
+        if metric <= watchdog.threshold {
+            watchdog.above_since = None;
+            return false;
+        }
+        let above_since = *watchdog.above_since.get_or_insert_with(Instant::now);
+        if watchdog.tripped || above_since.elapsed() < watchdog.duration {
+            return false;
+        }
+        watchdog.tripped = true;
+        true
+    }
+
+    /// Reset state which tracks the contention metric, along with the metric itself.
+    fn reset(&mut self) {
+        if let Some(hysteresis) = self.hysteresis.as_mut() {
+            hysteresis.alerting = false;
+        }
+        if let Some(watchdog) = self.watchdog.as_mut() {
+            watchdog.above_since = None;
+            watchdog.tripped = false;
+        }
+    }
+}
+
+/// Call a user callback from the monitoring thread. Exceptions are turned into
+/// warnings, rather than taking down the monitoring thread.
+fn invoke_callback(callback: &PyObject, args: impl IntoPy<Py<pyo3::types::PyTuple>>) {
+    Python::with_gil(|py| {
+        if let Err(e) = callback.call1(py, args) {
+            let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+            PyErr::warn(py, warning, &format!("Exception in callback: {}", e), 0)
+                .unwrap_or_else(|e| e.print(py));
+        }
+    })
+}
+
+/// Log the contention metric at INFO level from the monitoring thread. As with callbacks,
+/// failures are turned into warnings rather than taking down the monitoring thread.
+fn log_contention(logger_name: &str, metric: f64) {
+    Python::with_gil(|py| {
+        let result = py
+            .import("logging")
+            .and_then(|logging| logging.call_method1("getLogger", (logger_name,)))
+            .and_then(|logger| logger.call_method1("info", ("GIL contention: %.4f", metric)));
+        if let Err(e) = result {
+            let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+            PyErr::warn(py, warning, &format!("Exception in logging: {}", e), 0)
+                .unwrap_or_else(|e| e.print(py));
+        }
+    })
+}
+
+/// Write all of ``bytes`` to the raw file descriptor ``fd``, without taking ownership of
+/// it or needing the GIL.
+#[cfg(unix)]
+fn write_fd(fd: i32, bytes: &[u8]) -> std::io::Result<()> {
+    use std::os::unix::io::FromRawFd;
+    // SAFETY: the file is never dropped, so the caller's descriptor is left open
+    let mut file = std::mem::ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    file.write_all(bytes)
+}
+
+#[cfg(not(unix))]
+fn write_fd(_fd: i32, _bytes: &[u8]) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "streaming to a file descriptor requires Unix",
+    ))
+}
+
+/// Call an OpenTelemetry observer from the monitoring thread with a data point dict of
+/// the contention metric. As with callbacks, exceptions are turned into warnings.
+fn invoke_otel_callback(callback: &PyObject, metric: f64, time_unix_nano: u64) {
+    Python::with_gil(|py| {
+        let point = pyo3::types::PyDict::new(py);
+        let result = point
+            .set_item("name", "gilknocker.contention_metric")
+            .and_then(|_| point.set_item("unit", "1"))
+            .and_then(|_| point.set_item("value", metric))
+            .and_then(|_| point.set_item("time_unix_nano", time_unix_nano))
+            .and_then(|_| callback.call1(py, (point,)));
+        if let Err(e) = result {
+            let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+            PyErr::warn(py, warning, &format!("Exception in callback: {}", e), 0)
+                .unwrap_or_else(|e| e.print(py));
+        }
+    })
+}
+
+/// Call a ``set_final_callback`` callback with a dict summarising the final metrics.
+/// As with callbacks, exceptions are turned into warnings.
+fn invoke_final_callback(
+    py: Python,
+    callback: &PyObject,
+    (contention_metric, peak_contention_metric, num_samples, elapsed_secs): (f64, f64, u64, f64),
+) {
+    let summary = pyo3::types::PyDict::new(py);
+    let result = summary
+        .set_item("contention_metric", contention_metric)
+        .and_then(|_| summary.set_item("peak_contention_metric", peak_contention_metric))
+        .and_then(|_| summary.set_item("num_samples", num_samples))
+        .and_then(|_| summary.set_item("elapsed_secs", elapsed_secs))
+        .and_then(|_| callback.call1(py, (summary,)));
+    if let Err(e) = result {
+        let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+        PyErr::warn(py, warning, &format!("Exception in callback: {}", e), 0)
+            .unwrap_or_else(|e| e.print(py));
+    }
+}
+
+/// Immutable snapshot of a knocker's metrics, taken at a single point in time
+/// with ``KnockKnock.snapshot()``.
+#[pyclass(name = "Snapshot", module = "gilknocker", frozen)]
+pub struct Snapshot {
+    /// Contention metric at the time of the snapshot.
+    #[pyo3(get)]
+    contention_metric: f64,
+    /// Peak contention metric at the time of the snapshot.
+    #[pyo3(get)]
+    peak_contention_metric: f64,
+    /// Number of completed samples at the time of the snapshot.
+    #[pyo3(get)]
+    num_samples: u64,
+    /// Seconds spent monitoring at the time of the snapshot.
+    #[pyo3(get)]
+    elapsed_secs: f64,
+    /// Cumulative time spent waiting to acquire the GIL at the time of the snapshot.
+    #[pyo3(get)]
+    total_time_waiting_micros: u64,
+    /// Cumulative time spent sampling the GIL at the time of the snapshot.
+    #[pyo3(get)]
+    total_time_sampling_micros: u64,
+}
+
+#[pymethods]
+impl Snapshot {
+    #[new]
+    #[pyo3(signature = (
+        contention_metric = 0.0,
+        peak_contention_metric = 0.0,
+        num_samples = 0,
+        elapsed_secs = 0.0,
+        total_time_waiting_micros = 0,
+        total_time_sampling_micros = 0,
+    ))]
+    fn __new__(
+        contention_metric: f64,
+        peak_contention_metric: f64,
+        num_samples: u64,
+        elapsed_secs: f64,
+        total_time_waiting_micros: u64,
+        total_time_sampling_micros: u64,
+    ) -> Self {
+        Snapshot {
+            contention_metric,
+            peak_contention_metric,
+            num_samples,
+            elapsed_secs,
+            total_time_waiting_micros,
+            total_time_sampling_micros,
+        }
+    }
+
+    /// Difference between this snapshot and an ``other``, earlier, snapshot as a dict of
+    /// ``delta_time_waiting_micros``, ``delta_time_sampling_micros`` and the
+    /// ``contention_metric`` of only the samples taken between the two snapshots.
+    /// If no sampling happened in between, the contention metric is 0.
+    pub fn diff<'py>(
+        &self,
+        py: Python<'py>,
+        other: &Snapshot,
+    ) -> PyResult<&'py pyo3::types::PyDict> {
+        let delta_time_waiting_micros = self
+            .total_time_waiting_micros
+            .saturating_sub(other.total_time_waiting_micros);
+        let delta_time_sampling_micros = self
+            .total_time_sampling_micros
+            .saturating_sub(other.total_time_sampling_micros);
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("delta_time_waiting_micros", delta_time_waiting_micros)?;
+        dict.set_item("delta_time_sampling_micros", delta_time_sampling_micros)?;
+        dict.set_item(
+            "contention_metric",
+            contention_ratio(
+                Duration::from_micros(delta_time_waiting_micros),
+                Duration::from_micros(delta_time_sampling_micros),
+            ),
+        )?;
+        Ok(dict)
+    }
+}
+
+/// Raw accumulators of a knocker, from ``KnockKnock.mergeable_stats()``, which can be
+/// serialized, e.g. to send from worker processes to a parent, and merged there to compute
+/// the contention metric over all of them.
+#[pyclass(name = "MergeableStats", module = "gilknocker", frozen)]
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MergeableStats {
+    /// Cumulative time spent waiting to acquire the GIL.
+    #[pyo3(get)]
+    total_time_waiting_micros: u64,
+    /// Cumulative time spent sampling the GIL.
+    #[pyo3(get)]
+    total_time_sampling_micros: u64,
+    /// Number of completed samples.
+    #[pyo3(get)]
+    num_samples: u64,
+}
+
+#[pymethods]
+impl MergeableStats {
+    #[new]
+    #[pyo3(signature = (
+        total_time_waiting_micros = 0,
+        total_time_sampling_micros = 0,
+        num_samples = 0,
+    ))]
+    fn __new__(
+        total_time_waiting_micros: u64,
+        total_time_sampling_micros: u64,
+        num_samples: u64,
+    ) -> Self {
+        MergeableStats {
+            total_time_waiting_micros,
+            total_time_sampling_micros,
+            num_samples,
+        }
+    }
+
+    /// Ratio of the total time waiting to the total time sampling, pooled over all
+    /// merged stats.
+    #[getter]
+    pub fn contention_metric(&self) -> f64 {
+        contention_ratio(
+            Duration::from_micros(self.total_time_waiting_micros),
+            Duration::from_micros(self.total_time_sampling_micros),
+        )
+    }
+
+    /// Sum of these and ``other`` stats.
+    pub fn merge(&self, other: &MergeableStats) -> MergeableStats {
+        MergeableStats {
+            total_time_waiting_micros: self
+                .total_time_waiting_micros
+                .saturating_add(other.total_time_waiting_micros),
+            total_time_sampling_micros: self
+                .total_time_sampling_micros
+                .saturating_add(other.total_time_sampling_micros),
+            num_samples: self.num_samples.saturating_add(other.num_samples),
+        }
+    }
+
+    /// Serialize to bytes, to be read back with ``from_bytes()``.
+    pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py pyo3::types::PyBytes> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+        Ok(pyo3::types::PyBytes::new(py, &bytes))
+    }
+
+    /// Deserialize stats written by ``to_bytes()``, raising ``ValueError`` if malformed.
+    #[staticmethod]
+    pub fn from_bytes(bytes: &[u8]) -> PyResult<MergeableStats> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MergeableStats(total_time_waiting_micros={}, total_time_sampling_micros={}, num_samples={})",
+            self.total_time_waiting_micros, self.total_time_sampling_micros, self.num_samples
+        )
+    }
+}
+
+/// Fluent builder for ``KnockKnock``, see ``KnockKnock.builder()``. Anything not
+/// set gets the same default as the ``KnockKnock`` constructor.
+///
+/// Example
+/// -------
+/// ```python
+/// from gilknocker import KnockKnock
+/// knocker = (
+///     KnockKnock.builder()
+///     .with_polling_micros(1_000)
+///     .with_sleeping_micros(50_000)
+///     .build()
+/// )
+/// ```
+#[pyclass(name = "KnockKnockBuilder", module = "gilknocker")]
+#[derive(Default)]
+pub struct KnockKnockBuilder {
+    polling_interval_micros: Option<u64>,
+    sampling_interval_micros: Option<u64>,
+    sleeping_interval_micros: Option<u64>,
+    timeout_micros: Option<u64>,
+    ema_alpha: Option<f64>,
+    window_secs: Option<f64>,
+    history_size: Option<usize>,
+}
+
+#[pymethods]
+impl KnockKnockBuilder {
+    #[new]
+    fn __new__() -> Self {
+        Self::default()
+    }
+
+    /// Set ``polling_interval_micros``
+    fn with_polling_micros(mut slf: PyRefMut<'_, Self>, micros: u64) -> PyRefMut<'_, Self> {
+        slf.polling_interval_micros = Some(micros);
+        slf
+    }
+
+    /// Set ``sampling_interval_micros``
+    fn with_sampling_micros(mut slf: PyRefMut<'_, Self>, micros: u64) -> PyRefMut<'_, Self> {
+        slf.sampling_interval_micros = Some(micros);
+        slf
+    }
+
+    /// Set ``sleeping_interval_micros``
+    fn with_sleeping_micros(mut slf: PyRefMut<'_, Self>, micros: u64) -> PyRefMut<'_, Self> {
+        slf.sleeping_interval_micros = Some(micros);
+        slf
+    }
+
+    /// Set ``timeout_micros``
+    fn with_timeout_micros(mut slf: PyRefMut<'_, Self>, micros: u64) -> PyRefMut<'_, Self> {
+        slf.timeout_micros = Some(micros);
+        slf
+    }
+
+    /// Set ``ema_alpha``
+    fn with_ema_alpha(mut slf: PyRefMut<'_, Self>, alpha: f64) -> PyRefMut<'_, Self> {
+        slf.ema_alpha = Some(alpha);
+        slf
+    }
+
+    /// Set ``window_secs``
+    fn with_window_secs(mut slf: PyRefMut<'_, Self>, secs: f64) -> PyRefMut<'_, Self> {
+        slf.window_secs = Some(secs);
+        slf
+    }
+
+    /// Set ``history_size``
+    fn with_history_size(mut slf: PyRefMut<'_, Self>, size: usize) -> PyRefMut<'_, Self> {
+        slf.history_size = Some(size);
+        slf
+    }
+
+    /// Build the ``KnockKnock``, validating the configuration as its constructor does.
+    fn build(&self, py: Python) -> PyResult<KnockKnock> {
+        KnockKnock::__new__(
+            py,
+            self.polling_interval_micros,
+            self.sampling_interval_micros,
+            self.sleeping_interval_micros,
+            self.timeout_micros,
+            self.ema_alpha,
+            self.window_secs,
+            self.history_size,
+        )
+    }
+}
 
 /// Struct for polling, knocking on the GIL,
 /// checking if it's locked in the current thread
@@ -43,17 +1140,43 @@ struct Ack;
 /// knocker.stop()
 /// knocker.contention_metric  # float between 0-1 indicating GIL contention
 /// ```
-#[pyclass(name = "KnockKnock")]
+#[pyclass(name = "KnockKnock", module = "gilknocker", weakref)]
 #[derive(Default)]
 pub struct KnockKnock {
     handle: Option<thread::JoinHandle<()>>,
     tx: Option<Sender<Message>>,
     rx: Option<Receiver<Ack>>,
-    contention_metric: Arc<RwLock<f32>>,
+    stats: Arc<Stats>,
+    callbacks: Arc<Mutex<Callbacks>>,
+    exit_signal: Arc<ExitSignal>,
+    start_instant: Option<Instant>,
+    elapsed: Duration,
+    /// When paused, if paused, and the total time paused since starting or the last reset.
+    paused_at: Option<Instant>,
+    time_paused: Duration,
     polling_interval: Duration,
     sampling_interval: Duration,
     sleeping_interval: Duration,
     timeout: Duration,
+    ema_alpha: Option<f64>,
+    window: Option<Duration>,
+    history_size: usize,
+    generation: u64,
+    gil_disabled: bool,
+    thread_name_prefix: String,
+    /// ``(min, max)`` polling interval when adapting it to contention.
+    adaptive: Option<(Duration, Duration)>,
+    min_valid_samples: u64,
+    spin: bool,
+    single_probe: bool,
+    /// OS thread id of the monitoring thread last started, where supported.
+    monitor_thread_id: Option<u64>,
+    round_digits: Option<u32>,
+    /// Called, with the GIL held, once the monitoring thread is started and joined.
+    on_start: Option<PyObject>,
+    on_stop: Option<PyObject>,
+    /// Called with a summary of the final metrics once the monitoring thread is joined.
+    on_final: Option<PyObject>,
 }
 
 #[pymethods]
@@ -77,13 +1200,52 @@ impl KnockKnock {
     /// timeout_micros: Optional[int]
     ///     Timeout when attempting to stop or send messages to monitoring thread. Defaults to
     ///     max(sleeping_interval_micros, sampling_interval_micros, polling_interval_micros) + 1ms
+    /// ema_alpha: Optional[float]
+    ///     If set, the contention metric is an exponential moving average of each sample's
+    ///     contention, weighted by this smoothing factor in (0, 1] and by how long the sample
+    ///     actually lasted, instead of the cumulative ratio since starting. Resetting the
+    ///     contention metric clears the average to 0.
+    /// window_secs: Optional[float]
+    ///     If set, the contention metric only considers samples completed within the last
+    ///     ``window_secs`` seconds, instead of all samples since starting. Cannot be combined
+    ///     with ``ema_alpha``.
+    /// history_size: Optional[int]
+    ///     Number of the most recent per-sample contention ratios to retain, see ``history()``.
+    ///     Defaults to 1024.
     #[new]
+    #[allow(clippy::too_many_arguments)]
     pub fn __new__(
+        py: Python,
         polling_interval_micros: Option<u64>,
         sampling_interval_micros: Option<u64>,
         sleeping_interval_micros: Option<u64>,
         timeout_micros: Option<u64>,
+        ema_alpha: Option<f64>,
+        window_secs: Option<f64>,
+        history_size: Option<usize>,
     ) -> PyResult<Self> {
+        if let Some(alpha) = ema_alpha {
+            if !(alpha > 0.0 && alpha <= 1.0) {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "ema_alpha must be in (0, 1], got {}",
+                    alpha
+                )));
+            }
+        }
+        let window = match window_secs {
+            Some(secs) if !(secs.is_finite() && secs > 0.0) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "window_secs must be a positive number of seconds, got {}",
+                    secs
+                )));
+            }
+            Some(_) if ema_alpha.is_some() => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "ema_alpha and window_secs are mutually exclusive",
+                ));
+            }
+            window_secs => window_secs.map(Duration::from_secs_f64),
+        };
         let polling_interval =
             Duration::from_micros(polling_interval_micros.unwrap_or_else(|| 1000));
         let sampling_interval = Duration::from_micros(
@@ -99,173 +1261,2457 @@ impl KnockKnock {
                     + 1_000,
             ),
         };
+        validate_interval("polling_interval_micros", polling_interval)?;
+        validate_interval("sampling_interval_micros", sampling_interval)?;
+        validate_interval("sleeping_interval_micros", sleeping_interval)?;
+        if polling_interval > sampling_interval {
+            let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+            PyErr::warn(
+                py,
+                warning,
+                "polling_interval_micros is greater than sampling_interval_micros, \
+                 so the GIL will be polled at most once per sampling window.",
+                0,
+            )?;
+        }
+        warn_short_timeout(py, timeout, polling_interval)?;
         Ok(KnockKnock {
             polling_interval,
             sampling_interval,
             sleeping_interval,
             timeout,
+            ema_alpha,
+            window,
+            history_size: history_size.unwrap_or(1024),
+            thread_name_prefix: "gilknocker".to_string(),
             ..Default::default()
         })
     }
 
-    /// Get the contention metric, not _specific_ meaning other than a higher
-    /// value (closer to 1) indicates increased contention when acquiring the GIL.
+    /// Create from a config dict, e.g. parsed from YAML or JSON, with any of the keys
+    /// ``polling_interval_micros``, ``sampling_interval_micros``, ``sleeping_interval_micros``
+    /// and ``timeout_micros``. Missing keys get the same defaults as the constructor, unknown
+    /// keys raise ``ValueError``. See ``config_dict()`` for the reverse.
+    #[staticmethod]
+    pub fn from_dict(py: Python, config: &pyo3::types::PyDict) -> PyResult<Self> {
+        const KEYS: [&str; 4] = [
+            "polling_interval_micros",
+            "sampling_interval_micros",
+            "sleeping_interval_micros",
+            "timeout_micros",
+        ];
+        let mut values: [Option<u64>; 4] = [None; 4];
+        for (key, value) in config {
+            let key: &str = key.extract()?;
+            match KEYS.iter().position(|known| *known == key) {
+                Some(i) => values[i] = value.extract()?,
+                None => {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "Unknown config key {:?}, expected one of {}",
+                        key,
+                        KEYS.join(", ")
+                    )))
+                }
+            }
+        }
+        let [polling, sampling, sleeping, timeout] = values;
+        KnockKnock::__new__(py, polling, sampling, sleeping, timeout, None, None, None)
+    }
+
+    /// Config of the intervals as a dict, which ``from_dict()`` accepts.
+    pub fn config_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py pyo3::types::PyDict> {
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("polling_interval_micros", self.polling_interval_micros())?;
+        dict.set_item("sampling_interval_micros", self.sampling_interval_micros())?;
+        dict.set_item("sleeping_interval_micros", self.sleeping_interval_micros())?;
+        dict.set_item("timeout_micros", self.timeout_micros())?;
+        Ok(dict)
+    }
+
+    /// Start building a ``KnockKnock`` with a fluent ``KnockKnockBuilder``.
+    #[staticmethod]
+    pub fn builder() -> KnockKnockBuilder {
+        KnockKnockBuilder::default()
+    }
+
+    /// Preset favouring minimal impact on the monitored program: polling every 1ms
+    /// during short 5ms sampling windows, sleeping 1s between them.
+    #[staticmethod]
+    pub fn low_overhead(py: Python) -> PyResult<Self> {
+        KnockKnock::__new__(
+            py,
+            Some(1_000),
+            Some(5_000),
+            Some(1_000_000),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Preset favouring an accurate contention metric: polling every 100us during
+    /// long 100ms sampling windows, sleeping only 10ms between them.
+    #[staticmethod]
+    pub fn high_accuracy(py: Python) -> PyResult<Self> {
+        KnockKnock::__new__(
+            py,
+            Some(100),
+            Some(100_000),
+            Some(10_000),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// Measure GIL contention for ``duration_secs`` seconds and return the final
+    /// contention metric. Blocks the calling thread for the duration, without holding
+    /// the GIL, so other threads can run the workload being measured.
+    #[staticmethod]
+    #[pyo3(signature = (
+        duration_secs,
+        polling_interval_micros = None,
+        sampling_interval_micros = None,
+        sleeping_interval_micros = None,
+    ))]
+    pub fn measure(
+        py: Python,
+        duration_secs: f64,
+        polling_interval_micros: Option<u64>,
+        sampling_interval_micros: Option<u64>,
+        sleeping_interval_micros: Option<u64>,
+    ) -> PyResult<f64> {
+        if !(duration_secs.is_finite() && duration_secs >= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "duration_secs must be a non-negative number of seconds, got {}",
+                duration_secs
+            )));
+        }
+        let knocker = Py::new(
+            py,
+            KnockKnock::__new__(
+                py,
+                polling_interval_micros,
+                sampling_interval_micros,
+                sleeping_interval_micros,
+                None,
+                None,
+                None,
+                None,
+            )?,
+        )?;
+        KnockKnock::start(knocker.as_ref(py), false)?;
+        let duration = Duration::from_secs_f64(duration_secs);
+        py.allow_threads(move || thread::sleep(duration));
+        let metric = KnockKnock::stop(knocker.as_ref(py))?;
+        Ok(metric)
+    }
+
+    /// Call ``func(*args, **kwargs)`` while measuring the GIL contention it experiences,
+    /// returning ``(result, contention_metric)``. A fresh knocker with this one's config
+    /// does the measuring, so it's scoped to the call, and works whether this or any other
+    /// knocker is running. Any partial sampling window is included, so calls shorter than
+    /// ``sampling_interval_micros`` are still measured.
+    #[pyo3(signature = (func, *args, **kwargs))]
+    pub fn measure_call(
+        &self,
+        py: Python,
+        func: PyObject,
+        args: &pyo3::types::PyTuple,
+        kwargs: Option<&pyo3::types::PyDict>,
+    ) -> PyResult<(PyObject, f64)> {
+        let knocker = Py::new(
+            py,
+            KnockKnock {
+                polling_interval: self.polling_interval,
+                sampling_interval: self.sampling_interval,
+                sleeping_interval: self.sleeping_interval,
+                timeout: self.timeout,
+                ema_alpha: self.ema_alpha,
+                window: self.window,
+                history_size: self.history_size,
+                thread_name_prefix: self.thread_name_prefix.clone(),
+                adaptive: self.adaptive,
+                spin: self.spin,
+                single_probe: self.single_probe,
+                ..Default::default()
+            },
+        )?;
+        KnockKnock::start(knocker.as_ref(py), false)?;
+        let result = func.call(py, args, kwargs);
+        let metric = KnockKnock::stop(knocker.as_ref(py))?;
+        Ok((result?, metric))
+    }
+
+    /// Get the contention metric, not _specific_ meaning other than a higher
+    /// value (closer to 1) indicates increased contention when acquiring the GIL.
     /// and lower indicates less contention, with 0 theoretically indicating zero
     /// contention.
+    ///
+    /// On free-threaded builds running with the GIL disabled, this is always -1.
+    #[getter]
+    pub fn contention_metric(&self) -> f64 {
+        self.round(self.current_metric())
+    }
+
+    /// Whether the GIL was found disabled when last started, on free-threaded builds,
+    /// in which case there's no contention to monitor and no thread is started.
+    #[getter]
+    pub fn is_gil_disabled(&self) -> bool {
+        self.gil_disabled
+    }
+
+    /// Contention ratio of only the most recently completed sample, for a live gauge
+    /// rather than a trend. 0 until a sample completes.
+    #[getter]
+    pub fn instant_contention_metric(&self) -> f64 {
+        self.stats.instant_contention_metric()
+    }
+
+    /// Decimal places ``contention_metric`` and ``contention_metric_pct`` are rounded to,
+    /// with ties to even, or ``None`` (the default) for no rounding. Only the returned values
+    /// are rounded, not the accumulated metrics.
+    #[getter]
+    pub fn round_digits(&self) -> Option<u32> {
+        self.round_digits
+    }
+
+    /// Set ``round_digits``
+    #[setter]
+    pub fn set_round_digits(&mut self, round_digits: Option<u32>) {
+        self.round_digits = round_digits;
+    }
+
+    /// Number of samples which must complete, since starting or the last reset, before the
+    /// contention metric is considered valid, see ``is_metric_valid``. Defaults to 0.
+    #[getter]
+    pub fn min_valid_samples(&self) -> u64 {
+        self.min_valid_samples
+    }
+
+    /// Set ``min_valid_samples``
+    #[setter]
+    pub fn set_min_valid_samples(&mut self, min_valid_samples: u64) {
+        self.min_valid_samples = min_valid_samples;
+    }
+
+    /// Whether the monitoring thread appears to be collecting samples. ``False`` once more
+    /// than a few sampling cycles' worth of time was spent monitoring, since starting or the
+    /// last reset, without a single sample completing, e.g. when the thread is starved of
+    /// scheduling, so a dead monitor isn't mistaken for a contention metric of 0.
+    #[getter]
+    pub fn healthy(&self) -> bool {
+        let cycles = (self.sampling_interval + self.sleeping_interval) * HEALTHY_CYCLES;
+        self.num_samples() > 0 || self.monitored() <= cycles
+    }
+
+    /// Whether at least ``min_valid_samples`` samples have completed, since starting or
+    /// the last reset, so the contention metric isn't dominated by a single noisy sample.
+    /// Until then, dashboards can show the knocker as warming up.
+    #[getter]
+    pub fn is_metric_valid(&self) -> bool {
+        self.num_samples() >= self.min_valid_samples
+    }
+
+    /// Block until the next sample completes, or ``timeout_secs`` elapse, returning whether
+    /// a new sample arrived. The GIL is released while waiting, so tests and dashboards can
+    /// read fresh metrics without sleeping for a guessed interval.
+    pub fn wait_for_sample(&self, py: Python, timeout_secs: f64) -> PyResult<bool> {
+        if !(timeout_secs.is_finite() && timeout_secs >= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "timeout_secs must be a non-negative number of seconds, got {}",
+                timeout_secs
+            )));
+        }
+        let timeout = Duration::from_secs_f64(timeout_secs);
+        let stats = self.stats.clone();
+        Ok(py.allow_threads(move || stats.wait_for_sample(timeout)))
+    }
+
+    /// Smoothing factor of the exponential moving average, if enabled.
+    #[getter]
+    pub fn ema_alpha(&self) -> Option<f64> {
+        self.ema_alpha
+    }
+
+    /// Length of the sliding window in seconds, if enabled.
+    #[getter]
+    pub fn window_secs(&self) -> Option<f64> {
+        self.window.map(|window| window.as_secs_f64())
+    }
+
+    /// Count completed samples whose contention ratio exceeds ``threshold``, see
+    /// ``samples_over_threshold``. Changing it applies to samples completed from then on,
+    /// without resetting the count.
+    pub fn set_count_threshold(&mut self, threshold: f64) -> PyResult<()> {
+        if threshold.is_nan() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "threshold must be a number, got NaN",
+            ));
+        }
+        *self.stats.count_threshold.write() = Some(threshold);
+        Ok(())
+    }
+
+    /// Number of completed samples whose contention ratio exceeded the threshold set with
+    /// ``set_count_threshold``, since starting or the last reset.
+    #[getter]
+    pub fn samples_over_threshold(&self) -> u64 {
+        self.stats.samples_over_threshold.load(Ordering::Relaxed)
+    }
+
+    /// Percentiles, each in [0, 1], of the time taken to acquire the GIL on each poll, in
+    /// microseconds, over the most recent 4096 polls since starting or the last reset.
+    /// Uses the nearest-rank method, all are 0 before any polls.
+    #[pyo3(signature = (percentiles = vec![0.5, 0.95, 0.99]))]
+    pub fn latency_percentiles(&self, percentiles: Vec<f64>) -> PyResult<Vec<f64>> {
+        if let Some(p) = percentiles.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "percentiles must be in [0, 1], got {}",
+                p
+            )));
+        }
+        let mut latencies: Vec<Duration> = self.stats.latencies.lock().iter().copied().collect();
+        if latencies.is_empty() {
+            return Ok(vec![0_f64; percentiles.len()]);
+        }
+        latencies.sort_unstable();
+        Ok(percentiles
+            .iter()
+            .map(|p| {
+                let rank = (p * latencies.len() as f64).ceil() as usize;
+                latencies[rank.saturating_sub(1)].as_secs_f64() * 1e6
+            })
+            .collect())
+    }
+
+    /// Counts of completed samples by contention ratio, in ``buckets`` equal-width buckets
+    /// over [0, 1], lowest first. A ratio of exactly 1 is counted in the last bucket.
+    /// Ratios are binned to a resolution of 0.001, so bucket edges are accurate to that,
+    /// and ``buckets`` can be at most 1000. Where an edge falls within one of those bins,
+    /// e.g. 1/3, the whole bin is counted in the lower bucket.
+    pub fn histogram(&self, buckets: usize) -> PyResult<Vec<u64>> {
+        if buckets == 0 || buckets > HISTOGRAM_BINS {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "buckets must be between 1 and {}, got {}",
+                HISTOGRAM_BINS, buckets
+            )));
+        }
+        let mut counts = vec![0; buckets];
+        for (bin, count) in self.stats.histogram.lock().iter().enumerate() {
+            counts[(bin * buckets / HISTOGRAM_BINS).min(buckets - 1)] += count;
+        }
+        Ok(counts)
+    }
+
+    /// Contention ratios of the most recently completed samples, ordered oldest to newest.
+    pub fn history(&self) -> Vec<f64> {
+        self.stats
+            .history
+            .lock()
+            .iter()
+            .map(|(_, ratio, _, _)| *ratio)
+            .collect()
+    }
+
+    /// Contention ratios of the most recently completed samples, ordered oldest to newest,
+    /// as a numpy ``float64`` array. The ratios are copied once, straight into the memory
+    /// backing the array, avoiding conversion to and from a list of Python floats.
+    pub fn history_numpy<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        const WIDTH: usize = std::mem::size_of::<f64>();
+        let buffer = {
+            let history = self.stats.history.lock();
+            pyo3::types::PyByteArray::new_with(py, history.len() * WIDTH, |bytes| {
+                for (chunk, (_, ratio, _, _)) in bytes.chunks_exact_mut(WIDTH).zip(history.iter()) {
+                    chunk.copy_from_slice(&ratio.to_ne_bytes());
+                }
+                Ok(())
+            })?
+        };
+        // Not holding the lock, as importing numpy may run Python code and switch threads
+        py.import("numpy")?
+            .call_method1("frombuffer", (buffer, "float64"))
+    }
+
+    /// ``(timestamp, contention ratio)`` of the most recently completed samples, ordered
+    /// oldest to newest. Timestamps are seconds since ``start()`` or the last reset.
+    pub fn history_with_timestamps(&self) -> Vec<(f64, f64)> {
+        self.stats
+            .history
+            .lock()
+            .iter()
+            .map(|(timestamp, ratio, _, _)| (*timestamp, *ratio))
+            .collect()
+    }
+
+    /// Write the retained sample history, see ``history_size``, to a CSV file at ``path``
+    /// with columns ``timestamp_secs,ratio,time_waiting_micros,time_sampling_micros``,
+    /// oldest first. Raises ``OSError`` if it can't be written.
+    pub fn write_csv(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let history = self.stats.history.lock().clone();
+        py.allow_threads(move || -> std::io::Result<()> {
+            let mut file = BufWriter::new(File::create(path)?);
+            writeln!(
+                file,
+                "timestamp_secs,ratio,time_waiting_micros,time_sampling_micros"
+            )?;
+            for (timestamp, ratio, time_waiting, time_sampling) in history {
+                writeln!(
+                    file,
+                    "{},{},{},{}",
+                    timestamp,
+                    ratio,
+                    time_waiting.as_micros(),
+                    time_sampling.as_micros()
+                )?;
+            }
+            file.flush()
+        })?;
+        Ok(())
+    }
+
+    /// Highest contention metric seen since ``start()`` or the last reset.
+    #[getter]
+    pub fn peak_contention_metric(&self) -> f64 {
+        *self.stats.peak_contention_metric.read()
+    }
+
+    /// Highest per-sample contention ratio among retained samples, see ``history_size``,
+    /// completed within the last ``secs`` seconds, for a rolling "worst contention lately"
+    /// alarm. 0 when no samples completed within the window.
+    pub fn window_peak(&self, secs: f64) -> PyResult<f64> {
+        if !(secs.is_finite() && secs >= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "secs must be a non-negative number of seconds, got {}",
+                secs
+            )));
+        }
+        let now = match *self.stats.origin.read() {
+            Some(origin) => origin.elapsed().as_secs_f64(),
+            None => return Ok(0_f64),
+        };
+        let peak = self
+            .stats
+            .history
+            .lock()
+            .iter()
+            .rev()
+            .take_while(|(timestamp, ..)| *timestamp > now - secs)
+            .map(|(_, ratio, ..)| *ratio)
+            .fold(0_f64, f64::max);
+        Ok(peak)
+    }
+
+    /// The contention metric as a percentage, clamped to [0, 100] as accumulated
+    /// durations may push the raw metric slightly above 1.
+    #[getter]
+    pub fn contention_metric_pct(&self) -> f64 {
+        self.round((self.current_metric() * 100.0).clamp(0.0, 100.0))
+    }
+
+    /// Cumulative time spent waiting to acquire the GIL, in microseconds.
+    #[getter]
+    pub fn total_time_waiting_micros(&self) -> u64 {
+        self.raw_totals().0.as_micros() as u64
+    }
+
+    /// Cumulative time spent sampling the GIL, in microseconds.
     #[getter]
-    pub fn contention_metric(&self) -> f32 {
-        *(*self.contention_metric).read()
+    pub fn total_time_sampling_micros(&self) -> u64 {
+        self.raw_totals().1.as_micros() as u64
+    }
+
+    /// Cumulative time spent waiting to acquire the GIL, in seconds, e.g. for logging
+    /// "spent 1.2s waiting on the GIL out of 10s monitored". Reset with the metric.
+    #[getter]
+    pub fn total_wait_secs(&self) -> f64 {
+        self.raw_totals().0.as_secs_f64()
+    }
+
+    /// Cumulative time spent sampling the GIL, in seconds, of which ``total_wait_secs`` was
+    /// spent waiting for it. Sleeping between sampling windows isn't included, so without
+    /// ``ema_alpha`` or ``window_secs``, ``total_wait_secs / total_monitored_secs`` is the
+    /// contention metric. Reset with the metric.
+    #[getter]
+    pub fn total_monitored_secs(&self) -> f64 {
+        self.raw_totals().1.as_secs_f64()
+    }
+
+    /// Number of times the GIL was acquired in the last completed sampling window. Far
+    /// fewer than ``sampling_interval_micros / polling_interval_micros`` indicates the
+    /// OS isn't honouring the polling interval, or the GIL is heavily contended.
+    #[getter]
+    pub fn polls_per_window(&self) -> u64 {
+        self.stats.last_polls.load(Ordering::Relaxed)
+    }
+
+    /// Average number of times the GIL was acquired per completed sampling window,
+    /// 0 before any complete.
+    #[getter]
+    pub fn avg_polls_per_window(&self) -> f64 {
+        let _totals = self.stats.totals.read(); // consistent with ``num_samples``
+        match self.stats.num_samples.load(Ordering::Relaxed) {
+            0 => 0_f64,
+            num_samples => {
+                self.stats.total_polls.load(Ordering::Relaxed) as f64 / num_samples as f64
+            }
+        }
+    }
+
+    /// Fraction of the monitoring thread's time spent sampling the GIL rather than sleeping
+    /// between sampling windows, an indication of monitoring overhead. 0 before any sampling.
+    #[getter]
+    pub fn duty_cycle(&self) -> f64 {
+        let time_sampling = self.stats.totals.read().1.as_secs_f64();
+        let time_sleeping = self.stats.time_sleeping.read().as_secs_f64();
+        if time_sampling + time_sleeping == 0.0 {
+            return 0_f64;
+        }
+        time_sampling / (time_sampling + time_sleeping)
+    }
+
+    /// Estimated percentage of one CPU core the monitoring thread uses, from the configured
+    /// intervals and the cost of a poll, taken as the fastest recent GIL acquisition, see
+    /// ``latency_percentiles``. The poll cost isn't calibrated by ``start()``, which would
+    /// delay it, so this is ``None`` before starting, and after a reset, until the
+    /// monitoring thread has polled again. When spinning, see ``spin``, the thread is busy
+    /// throughout each sampling window. It doesn't account for time waiting on a contended
+    /// GIL, during which the thread is blocked, nor for callbacks.
+    #[getter]
+    pub fn estimated_overhead_pct(&self) -> Option<f64> {
+        let poll_cost = self.stats.latencies.lock().iter().min()?.as_secs_f64();
+        let sampling = self.sampling_interval.as_secs_f64();
+        let cycle = sampling + self.sleeping_interval.as_secs_f64();
+        let busy = if self.spin && self.polling_interval < SPIN_THRESHOLD {
+            sampling
+        } else if self.single_probe {
+            poll_cost
+        } else {
+            let polls = (sampling / self.polling_interval.as_secs_f64()).ceil();
+            (polls * poll_cost).min(sampling)
+        };
+        Some(100.0 * busy / cycle)
+    }
+
+    /// Number of sampling windows which have completed.
+    #[getter]
+    pub fn num_samples(&self) -> u64 {
+        self.stats.num_samples.load(Ordering::Relaxed)
+    }
+
+    /// Seconds spent monitoring since ``start()`` or the last reset, frozen once stopped.
+    /// Time spent paused isn't included.
+    #[getter]
+    pub fn elapsed_secs(&self) -> f64 {
+        self.monitored().as_secs_f64()
+    }
+
+    /// Sampling windows completed per second of ``elapsed_secs``, at most about
+    /// ``1 / (sampling + sleeping interval)`` and lower when the monitoring thread falls
+    /// behind, e.g. waiting for the GIL. 0 before the first sample, reset with the metric.
+    #[getter]
+    pub fn sample_rate(&self) -> f64 {
+        let num_samples = self.num_samples();
+        let elapsed_secs = self.elapsed_secs();
+        if num_samples == 0 || elapsed_secs == 0.0 {
+            return 0.0;
+        }
+        num_samples as f64 / elapsed_secs
+    }
+
+    /// Take an immutable snapshot of the metrics, consistent with each other.
+    pub fn snapshot(&self) -> Snapshot {
+        let (contention_metric, peak_contention_metric, num_samples, totals) =
+            self.stats.snapshot();
+        Snapshot {
+            contention_metric,
+            peak_contention_metric,
+            num_samples,
+            elapsed_secs: self.elapsed_secs(),
+            total_time_waiting_micros: totals.0.as_micros() as u64,
+            total_time_sampling_micros: totals.1.as_micros() as u64,
+        }
+    }
+
+    /// Raw accumulators of the metrics, consistent with each other, to serialize and
+    /// merge with those of other knockers, e.g. in other processes.
+    pub fn mergeable_stats(&self) -> MergeableStats {
+        let (_, _, num_samples, totals) = self.stats.snapshot();
+        MergeableStats {
+            total_time_waiting_micros: totals.0.as_micros() as u64,
+            total_time_sampling_micros: totals.1.as_micros() as u64,
+            num_samples,
+        }
+    }
+
+    /// Call ``callback(contention_metric)`` from the monitoring thread when the contention
+    /// metric rises above ``threshold``, and ``on_fall(contention_metric)``, if given, when it
+    /// falls back to or below it. Only crossings fire, not every sample above the threshold.
+    /// Passing ``None`` as the callback clears it.
+    ///
+    /// The callbacks need the GIL, so they're delayed while it's contended, and delay the
+    /// monitoring thread while they run; keep them short. They run while ``stop()`` waits for
+    /// the monitoring thread too, where accessing this knocker from them will fail.
+    /// Exceptions raised from them are turned into warnings.
+    #[pyo3(signature = (threshold, callback, on_fall = None))]
+    pub fn set_threshold_callback(
+        &mut self,
+        threshold: f64,
+        callback: Option<PyObject>,
+        on_fall: Option<PyObject>,
+    ) {
+        self.callbacks.lock().threshold = callback.map(|on_rise| ThresholdCallback {
+            threshold,
+            on_rise,
+            on_fall,
+            above: false,
+        });
+    }
+
+    /// Call ``on_start()`` from ``start()`` once the monitoring thread is spawned, and
+    /// ``on_stop()`` from ``stop()`` once it has exited and been joined, e.g. to mark
+    /// monitoring on external dashboards. Both are called with the GIL held, passing
+    /// ``None`` clears them. Accessing this knocker from ``on_stop`` will fail, as ``stop()``
+    /// is still running. Exceptions raised from them are turned into warnings.
+    #[pyo3(signature = (on_start = None, on_stop = None))]
+    pub fn set_lifecycle_callbacks(
+        &mut self,
+        on_start: Option<PyObject>,
+        on_stop: Option<PyObject>,
+    ) {
+        self.on_start = on_start;
+        self.on_stop = on_stop;
+    }
+
+    /// Call ``callback(summary)`` exactly once per ``stop()``, after the monitoring thread is
+    /// joined, with a dict of the final ``contention_metric``, ``peak_contention_metric``,
+    /// ``num_samples`` and ``elapsed_secs``, e.g. to ship a final summary. It's called with
+    /// the GIL held, from ``stop()``, or if that timed out waiting for the thread, from a
+    /// background thread once it does exit. Passing ``None`` clears it. Exceptions raised
+    /// from it are turned into warnings.
+    pub fn set_final_callback(&mut self, callback: Option<PyObject>) {
+        self.on_final = callback;
+    }
+
+    /// Call ``callback(ratio, time_waiting_micros, time_sampling_micros)`` from the monitoring
+    /// thread each time a sample completes, passing ``None`` clears it. The same caveats as
+    /// ``set_threshold_callback`` apply.
+    pub fn set_sample_callback(&mut self, callback: Option<PyObject>) {
+        self.callbacks.lock().sample = callback;
+    }
+
+    /// Call ``callback(contention_metric)`` from the monitoring thread every
+    /// ``interval_secs`` seconds while running, e.g. as a heartbeat for dashboards, on its
+    /// own timer rather than as samples complete. It needs the GIL, so is delayed while it's
+    /// contended, and missed heartbeats aren't caught up on. It doesn't fire while paused.
+    /// Passing ``None`` as the callback clears it. The same caveats as
+    /// ``set_threshold_callback`` apply.
+    pub fn set_heartbeat(
+        &mut self,
+        interval_secs: f64,
+        callback: Option<PyObject>,
+    ) -> PyResult<()> {
+        if !(interval_secs.is_finite() && interval_secs > 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "interval_secs must be a positive number of seconds, got {}",
+                interval_secs
+            )));
+        }
+        let every = Duration::from_secs_f64(interval_secs);
+        self.callbacks.lock().heartbeat = callback.map(|callback| Heartbeat {
+            every,
+            callback,
+            next: Instant::now() + every,
+        });
+        Ok(())
+    }
+
+    /// Call ``callback(overrun_micros)`` from the monitoring thread when a sampling window
+    /// takes longer than ``sampling_interval_micros + sleeping_interval_micros``, e.g.
+    /// held open waiting for the GIL, so the sampling cadence drifts. ``overrun_micros`` is
+    /// by how much it took longer, passing ``None`` clears it. Counted in ``overrun_count``
+    /// either way. The same caveats as ``set_threshold_callback`` apply.
+    pub fn set_overrun_callback(&mut self, callback: Option<PyObject>) {
+        self.callbacks.lock().overrun = callback;
+    }
+
+    /// Number of sampling windows which overran, see ``set_overrun_callback``, since
+    /// starting or the last reset.
+    #[getter]
+    pub fn overrun_count(&self) -> u64 {
+        self.stats.overrun_count.load(Ordering::Relaxed)
+    }
+
+    /// Call ``on_enter(contention_metric)`` from the monitoring thread when the contention
+    /// metric rises above ``high``, then ``on_exit(contention_metric)`` only once it drops
+    /// below ``low``, avoiding flapping when it hovers around a single threshold. Resetting
+    /// the contention metric also resets the alerting state. The same caveats as
+    /// ``set_threshold_callback`` apply.
+    pub fn set_hysteresis(
+        &mut self,
+        high: f64,
+        low: f64,
+        on_enter: PyObject,
+        on_exit: PyObject,
+    ) -> PyResult<()> {
+        if low.is_nan() || high.is_nan() || low >= high {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "low ({}) must be less than high ({})",
+                low, high
+            )));
+        }
+        self.callbacks.lock().hysteresis = Some(HysteresisCallbacks {
+            high,
+            low,
+            on_enter,
+            on_exit,
+            alerting: false,
+        });
+        Ok(())
+    }
+
+    /// Fire the sample, threshold and hysteresis callbacks at most every ``micros``
+    /// microseconds, with the most recent sample and contention metric, decoupling
+    /// how often callbacks acquire the GIL from how often samples complete.
+    /// Defaults to 0, firing callbacks for every sample.
+    pub fn set_min_callback_interval_micros(&mut self, micros: u64) {
+        self.callbacks.lock().min_interval = Duration::from_micros(micros);
+    }
+
+    /// Minimum time between firing callbacks, in microseconds.
+    #[getter]
+    pub fn min_callback_interval_micros(&self) -> u64 {
+        self.callbacks.lock().min_interval.as_micros() as u64
+    }
+
+    /// Interrupt the main thread, as if by ``SIGINT``, once the contention metric has stayed
+    /// above ``threshold`` for ``duration_secs`` seconds, to fail fast on sustained contention.
+    /// Passing ``None`` disables the watchdog.
+    ///
+    /// The monitoring thread calls ``PyErr_SetInterrupt``, so with Python's default ``SIGINT``
+    /// handler, ``KeyboardInterrupt`` is raised in the main thread the next time it runs
+    /// Python code. It isn't raised while the main thread is blocked in C code, such as in
+    /// a long ``time.sleep``, until that returns, and a custom ``SIGINT`` handler is called
+    /// instead. The metric is checked as each sample completes, and the watchdog trips at
+    /// most once, until the contention metric is reset.
+    #[pyo3(signature = (threshold, duration_secs = 0.0))]
+    pub fn set_watchdog(&mut self, threshold: Option<f64>, duration_secs: f64) -> PyResult<()> {
+        if !(duration_secs.is_finite() && duration_secs >= 0.0) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "duration_secs must be a non-negative number of seconds, got {}",
+                duration_secs
+            )));
+        }
+        self.callbacks.lock().watchdog = match threshold {
+            Some(threshold) if threshold.is_nan() => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "threshold must be a number, got NaN",
+                ));
+            }
+            Some(threshold) => Some(Watchdog {
+                threshold,
+                duration: Duration::from_secs_f64(duration_secs),
+                above_since: None,
+                tripped: false,
+            }),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Push point for OpenTelemetry, e.g. to record into an ``ObservableGauge`` or build an
+    /// OTLP data point without polling. The monitoring thread calls ``callback(point)`` as each
+    /// sample completes, subject to ``set_min_callback_interval_micros``, where ``point`` is a
+    /// dict of ``name`` (``"gilknocker.contention_metric"``), ``unit`` (``"1"``), ``value``,
+    /// the contention metric, and ``time_unix_nano``, a wall clock timestamp which never goes
+    /// backwards. Passing ``None`` clears it. The same caveats as ``set_threshold_callback``
+    /// apply.
+    pub fn otel_observe(&mut self, callback: Option<PyObject>) {
+        self.callbacks.lock().otel = callback;
+    }
+
+    /// Asynchronous iterator of ``(timestamp, metric)`` tuples as samples complete, for
+    /// ``async for timestamp, metric in knocker.stream():``, where ``timestamp`` is the Unix
+    /// time in seconds. Up to ``maxsize`` samples are buffered, beyond which the oldest are
+    /// dropped rather than blocking the monitoring thread. Iteration ends once the monitoring
+    /// thread exits, or the stream is closed with ``close()``, e.g. after breaking out.
+    /// If not running, the stream is already closed, ending iteration immediately.
+    #[pyo3(signature = (maxsize = 64))]
+    pub fn stream(&self, maxsize: usize) -> PyResult<SampleStream> {
+        if maxsize == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "maxsize must be at least 1",
+            ));
+        }
+        let queue = Arc::new(SampleQueue::new(maxsize));
+        let mut callbacks = self.callbacks.lock();
+        if callbacks.stream_source.is_some() {
+            callbacks.sample_queues.push(queue.clone());
+        } else {
+            // No monitoring thread to push samples, or close the stream
+            queue.close(false);
+        }
+        Ok(SampleStream { queue })
+    }
+
+    /// Stream a JSON Lines record, ``{"t": ..., "ratio": ..., "metric": ...}``, of every
+    /// ``every`` (default 1) completed samples to the open file descriptor ``fd``, e.g. of a
+    /// file or pipe, for continuous capture without polling. ``t`` is the Unix time in
+    /// seconds, ``ratio`` the sample's contention ratio and ``metric`` the contention metric.
+    /// The monitoring thread writes to ``fd`` directly, without the GIL, so it must stay open
+    /// while streaming. Passing ``None`` stops streaming. If a write fails, streaming is
+    /// disabled with a warning. Only supported on Unix.
+    #[pyo3(signature = (fd, every = None))]
+    pub fn stream_to_fd(&mut self, fd: Option<i32>, every: Option<usize>) -> PyResult<()> {
+        let stream = match fd {
+            None => None,
+            Some(_) if cfg!(not(unix)) => {
+                return Err(pyo3::exceptions::PyNotImplementedError::new_err(
+                    "Streaming to a file descriptor requires Unix",
+                ));
+            }
+            Some(fd) if fd < 0 => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "fd must be a non-negative file descriptor, got {}",
+                    fd
+                )));
+            }
+            Some(_) if every == Some(0) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(
+                    "every must be at least 1",
+                ));
+            }
+            Some(fd) => Some(Stream {
+                fd,
+                every: every.unwrap_or(1),
+                pending: 0,
+            }),
+        };
+        self.callbacks.lock().stream = stream;
+        Ok(())
+    }
+
+    /// Log the contention metric at INFO level to the ``logger_name`` logger, defaulting to
+    /// ``"gilknocker"``, at most every ``log_every_secs`` seconds as samples complete.
+    /// Passing ``None`` disables logging. Logging happens from the monitoring thread,
+    /// any failure to log is turned into a warning.
+    #[pyo3(signature = (log_every_secs, logger_name = None))]
+    pub fn set_logging(
+        &mut self,
+        log_every_secs: Option<f64>,
+        logger_name: Option<String>,
+    ) -> PyResult<()> {
+        let logging = match log_every_secs {
+            Some(secs) if !(secs.is_finite() && secs >= 0.0) => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "log_every_secs must be a non-negative number of seconds, got {}",
+                    secs
+                )));
+            }
+            Some(secs) => Some(Logging {
+                every: Duration::from_secs_f64(secs),
+                logger_name: logger_name.unwrap_or_else(|| "gilknocker".to_string()),
+                last: Instant::now(),
+            }),
+            None => None,
+        };
+        self.callbacks.lock().logging = logging;
+        Ok(())
+    }
+
+    /// How frequently the GIL is acquired while sampling, in microseconds.
+    #[getter]
+    pub fn polling_interval_micros(&self) -> u64 {
+        self.polling_interval.as_micros() as u64
+    }
+
+    /// Adapt the polling interval to contention, polling coarsely, every ``max_polling_micros``,
+    /// while the GIL is quiet and more finely, down to every ``min_polling_micros``, as
+    /// contention rises. After each sampling window, the polling interval is set to
+    /// ``max - ratio * (max - min)`` from that window's contention ratio, see
+    /// ``effective_polling_interval_micros``, which is always within the bounds. They
+    /// default to a tenth of, and ten times, ``polling_interval_micros``. Disabling it goes
+    /// back to ``polling_interval_micros``. Applies from the next poll if running.
+    #[pyo3(signature = (adaptive, min_polling_micros = None, max_polling_micros = None))]
+    pub fn set_adaptive_polling(
+        &mut self,
+        py: Python,
+        adaptive: bool,
+        min_polling_micros: Option<u64>,
+        max_polling_micros: Option<u64>,
+    ) -> PyResult<()> {
+        let bounds = if adaptive {
+            let polling_micros = self.polling_interval.as_micros() as u64;
+            let min_polling_micros = min_polling_micros.unwrap_or((polling_micros / 10).max(1));
+            let max_polling_micros = max_polling_micros.unwrap_or(polling_micros * 10);
+            let (min, max) = (
+                Duration::from_micros(min_polling_micros),
+                Duration::from_micros(max_polling_micros),
+            );
+            validate_interval("min_polling_micros", min)?;
+            validate_interval("max_polling_micros", max)?;
+            if min > max {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "min_polling_micros ({}) must not exceed max_polling_micros ({})",
+                    min_polling_micros, max_polling_micros
+                )));
+            }
+            Some((min, max))
+        } else {
+            None
+        };
+        self.send(py, Message::SetAdaptive(bounds))?;
+        self.adaptive = bounds;
+        Ok(())
+    }
+
+    /// Is the polling interval adapted to contention? See ``set_adaptive_polling``.
+    #[getter]
+    pub fn adaptive(&self) -> bool {
+        self.adaptive.is_some()
+    }
+
+    /// Lower bound of the adaptive polling interval in microseconds, ``None`` unless
+    /// adaptive, see ``set_adaptive_polling``.
+    #[getter]
+    pub fn adaptive_min_micros(&self) -> Option<u64> {
+        self.adaptive.map(|(min, _)| min.as_micros() as u64)
+    }
+
+    /// Upper bound of the adaptive polling interval in microseconds, ``None`` unless
+    /// adaptive, see ``set_adaptive_polling``.
+    #[getter]
+    pub fn adaptive_max_micros(&self) -> Option<u64> {
+        self.adaptive.map(|(_, max)| max.as_micros() as u64)
+    }
+
+    /// Polling interval currently used, in microseconds, which only differs from
+    /// ``polling_interval_micros`` when adaptive, see ``set_adaptive_polling``.
+    #[getter]
+    pub fn effective_polling_interval_micros(&self) -> u64 {
+        if self.is_running() {
+            self.stats.effective_polling_micros.load(Ordering::Relaxed)
+        } else {
+            adaptive_polling_interval(self.polling_interval, self.adaptive, 0.0).as_micros() as u64
+        }
+    }
+
+    /// Change how frequently the GIL is acquired while sampling, without losing the
+    /// accumulated metrics. If running, it applies from the next sampling window.
+    pub fn set_polling_interval_micros(&mut self, py: Python, micros: u64) -> PyResult<()> {
+        let interval = Duration::from_micros(micros);
+        validate_interval("polling_interval_micros", interval)?;
+        self.send(py, Message::SetPolling(interval))?;
+        self.polling_interval = interval;
+        Ok(())
+    }
+
+    /// How long each sampling window lasts, in microseconds.
+    #[getter]
+    pub fn sampling_interval_micros(&self) -> u64 {
+        self.sampling_interval.as_micros() as u64
+    }
+
+    /// Change how long each sampling window lasts, without losing the accumulated
+    /// metrics. If running, it applies from the next sampling window. The timeout
+    /// for messages to the monitoring thread isn't recalculated.
+    pub fn set_sampling_interval_micros(&mut self, py: Python, micros: u64) -> PyResult<()> {
+        let interval = Duration::from_micros(micros);
+        validate_interval("sampling_interval_micros", interval)?;
+        self.send(py, Message::SetSampling(interval))?;
+        self.sampling_interval = interval;
+        Ok(())
+    }
+
+    /// How long to sleep between sampling windows, in microseconds.
+    #[getter]
+    pub fn sleeping_interval_micros(&self) -> u64 {
+        self.sleeping_interval.as_micros() as u64
+    }
+
+    /// Change how long to sleep between sampling windows, without losing the accumulated
+    /// metrics. If running, it applies from the monitoring thread's next wait. The timeout
+    /// for messages to the monitoring thread isn't recalculated.
+    pub fn set_sleeping_interval_micros(&mut self, py: Python, micros: u64) -> PyResult<()> {
+        let interval = Duration::from_micros(micros);
+        validate_interval("sleeping_interval_micros", interval)?;
+        self.send(py, Message::SetSleeping(interval))?;
+        self.sleeping_interval = interval;
+        Ok(())
+    }
+
+    /// How long to wait for the monitoring thread to acknowledge a reset or exit when
+    /// stopping, in microseconds.
+    #[getter]
+    pub fn timeout_micros(&self) -> u64 {
+        self.timeout.as_micros() as u64
+    }
+
+    /// Change how long to wait for the monitoring thread, e.g. after changing intervals,
+    /// applying to subsequent resets and stops.
+    pub fn set_timeout_micros(&mut self, py: Python, micros: u64) -> PyResult<()> {
+        let timeout = Duration::from_micros(micros);
+        validate_interval("timeout_micros", timeout)?;
+        warn_short_timeout(py, timeout, self.polling_interval)?;
+        self.timeout = timeout;
+        Ok(())
+    }
+
+    /// Snapshot of the current contention metric and configuration as a dict.
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py pyo3::types::PyDict> {
+        let state = self.state();
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("contention_metric", state.contention_metric)?;
+        dict.set_item("polling_interval_micros", state.polling_interval_micros)?;
+        dict.set_item("sampling_interval_micros", state.sampling_interval_micros)?;
+        dict.set_item("sleeping_interval_micros", state.sleeping_interval_micros)?;
+        dict.set_item("timeout_micros", state.timeout_micros)?;
+        dict.set_item("is_running", state.is_running)?;
+        Ok(dict)
+    }
+
+    /// Snapshot of the current contention metric and configuration as a JSON string,
+    /// with the same fields as ``to_dict()``.
+    pub fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.state())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Archive the configuration and aggregate metrics as a JSON string, with the same
+    /// fields as ``to_json()`` plus ``peak_contention_metric``, ``num_samples``,
+    /// ``total_time_waiting_micros``, ``total_time_sampling_micros``, ``elapsed_secs`` and
+    /// ``history_size``. With ``include_history``, ``history`` holds the retained samples,
+    /// at most ``history_size`` of them, oldest first, each as ``{"t": ..., "ratio": ...,
+    /// "time_waiting_micros": ..., "time_sampling_micros": ...}`` where ``t`` is seconds
+    /// since ``start()`` or the last reset. The aggregates and history are consistent.
+    #[pyo3(signature = (include_history = false))]
+    pub fn dump_json(&self, include_history: bool) -> PyResult<String> {
+        let dump = {
+            let totals = self.stats.totals.read();
+            let history = include_history.then(|| {
+                self.stats
+                    .history
+                    .lock()
+                    .iter()
+                    .map(|(t, ratio, time_waiting, time_sampling)| DumpedSample {
+                        t: *t,
+                        ratio: *ratio,
+                        time_waiting_micros: time_waiting.as_micros() as u64,
+                        time_sampling_micros: time_sampling.as_micros() as u64,
+                    })
+                    .collect()
+            });
+            Dump {
+                state: self.state(),
+                peak_contention_metric: self.peak_contention_metric(),
+                num_samples: self.num_samples(),
+                total_time_waiting_micros: totals.0.as_micros() as u64,
+                total_time_sampling_micros: totals.1.as_micros() as u64,
+                elapsed_secs: self.elapsed_secs(),
+                history_size: self.history_size,
+                history,
+            }
+        };
+        serde_json::to_string(&dump)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))
+    }
+
+    /// Current contention metric and configuration in the Prometheus text exposition
+    /// format, with each metric name prefixed by ``namespace`` if given.
+    #[pyo3(signature = (namespace = None))]
+    pub fn prometheus(&self, namespace: Option<&str>) -> String {
+        let state = self.state();
+        let prefix = namespace.map(|ns| format!("{}_", ns)).unwrap_or_default();
+        let gauges = [
+            (
+                "gil_contention_ratio",
+                "Ratio of time spent waiting to acquire the GIL while sampling.",
+                state.contention_metric,
+            ),
+            (
+                "gil_polling_interval_micros",
+                "Configured interval between GIL acquisitions while sampling.",
+                state.polling_interval_micros as f64,
+            ),
+            (
+                "gil_sampling_interval_micros",
+                "Configured duration of each sampling window.",
+                state.sampling_interval_micros as f64,
+            ),
+            (
+                "gil_sleeping_interval_micros",
+                "Configured duration between sampling windows.",
+                state.sleeping_interval_micros as f64,
+            ),
+        ];
+        gauges
+            .iter()
+            .map(|(name, help, value)| {
+                format!(
+                    "# HELP {prefix}{name} {help}\n# TYPE {prefix}{name} gauge\n{prefix}{name} {value}\n"
+                )
+            })
+            .collect()
+    }
+
+    /// Reset the contention metric/monitoring state
+    ///
+    /// Any partial sampling window at the time of the reset is discarded.
+    /// Returns whether the reset took: ``True`` when the monitoring thread acknowledged it
+    /// within ``timeout``, or there's no thread to acknowledge it, ``False`` (after a
+    /// warning) when it timed out, so callers can retry.
+    pub fn reset_contention_metric(slf: &PyCell<Self>) -> PyResult<bool> {
+        Ok(KnockKnock::reset(slf)?.0)
+    }
+
+    /// Return the contention metric and reset it in one step, for per-interval contention
+    /// in a poll-and-forward collector. The monitoring thread reads the metric as it resets
+    /// it, so no sample is counted in two reads or missed between them, unlike reading then
+    /// calling ``reset_contention_metric()``. Any partial sampling window is discarded.
+    pub fn read_and_reset(slf: &PyCell<Self>) -> PyResult<f64> {
+        let (_, metric) = KnockKnock::reset(slf)?;
+        let this = slf.borrow();
+        if this.gil_disabled {
+            return Ok(-1_f64);
+        }
+        Ok(this.round(metric))
+    }
+
+    /// Pause sampling, keeping the accumulated metrics, until ``resume()``. Any partial
+    /// sampling window is discarded. While paused, nothing is sampled or accumulated and
+    /// the time paused doesn't count towards ``elapsed_secs`` or ``duty_cycle``.
+    /// Does nothing if not running or already paused.
+    pub fn pause(&mut self, py: Python) -> PyResult<()> {
+        if self.is_running() && self.paused_at.is_none() {
+            self.send(py, Message::Pause)?;
+            self.paused_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Resume sampling after ``pause()``, starting a fresh sampling window.
+    /// Does nothing if not paused.
+    pub fn resume(&mut self, py: Python) -> PyResult<()> {
+        if let Some(paused_at) = take(&mut self.paused_at) {
+            self.send(py, Message::Resume)?;
+            self.time_paused += paused_at.elapsed();
+        }
+        Ok(())
+    }
+
+    /// Is sampling paused?
+    #[getter]
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Start polling the GIL to check if it's locked.
+    ///
+    /// Raises ``RuntimeError`` if already running, unless ``force`` is set, in which
+    /// case the running monitoring thread is stopped before starting a new one. Also
+    /// raises ``RuntimeError`` if the monitoring thread panicked and hasn't been stopped
+    /// since, see ``error``, after which it can be started again.
+    #[pyo3(signature = (force = false))]
+    fn start(slf: &PyCell<Self>, force: bool) -> PyResult<()> {
+        if slf.borrow().is_running() {
+            if !force {
+                return Err(pyo3::exceptions::PyRuntimeError::new_err(
+                    "KnockKnock is already running, call stop() first or use start(force=True)",
+                ));
+            }
+            KnockKnock::stop(slf)?;
+        } else if slf.borrow().handle.is_some() {
+            // The monitoring thread exited by itself, having panicked, so join it and raise
+            // its panic, rather than losing it by starting afresh
+            KnockKnock::stop(slf)?;
+        }
+        let mut slf = slf.borrow_mut();
+
+        // Free-threaded builds may run without the GIL, leaving nothing to contend for.
+        {
+            let py = slf.py();
+            let sys = py.import("sys")?;
+            slf.gil_disabled = sys.hasattr("_is_gil_enabled")?
+                && !sys.call_method0("_is_gil_enabled")?.extract::<bool>()?;
+            if slf.gil_disabled {
+                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                PyErr::warn(
+                    py,
+                    warning,
+                    "The GIL is disabled, not monitoring contention.",
+                    0,
+                )?;
+                return Ok(());
+            }
+        }
+
+        // The only process-wide state touched, and idempotent: initializing threads is a
+        // no-op once done (and always done from Python 3.7), so any number of knockers can
+        // run at once, each with its own monitoring thread and metrics.
+        unsafe {
+            if PyEval_ThreadsInitialized() == 0 {
+                PyEval_InitThreads();
+            }
+        }
+
+        // Stop the gilknocker thread at exit, which reduces the chance of odd
+        // 'no Python frame' core dumps when trying to acquire the GIL when the process
+        // has exited. Only a weak reference is kept, so the knocker can still be dropped.
+        {
+            let py = slf.py();
+            let __knocker = unsafe { PyObject::from_borrowed_ptr(py, slf.as_ptr()) };
+            exit_registry(py)?.call_method1("add", (__knocker.clone_ref(py),))?;
+            // The monitoring thread doesn't survive fork, so forget it in the child.
+            fork_registry(py)?.call_method1("add", (__knocker,))?;
+        }
+
+        let self_: &mut KnockKnock = slf.deref_mut();
+
+        // send messages to thread
+        let (tx, recv) = channel();
+        self_.tx = Some(tx);
+
+        // recieve messages from thread
+        let (send, rx) = channel();
+        self_.rx = Some(rx);
+
+        let stats = Arc::new(Stats {
+            ema_alpha: self_.ema_alpha,
+            window: self_.window,
+            history: Mutex::new(VecDeque::with_capacity(self_.history_size)),
+            history_size: self_.history_size,
+            origin: RwLock::new(Some(Instant::now())),
+            count_threshold: RwLock::new(*self_.stats.count_threshold.read()),
+            effective_polling_micros: AtomicU64::new(
+                adaptive_polling_interval(self_.polling_interval, self_.adaptive, 0.0).as_micros()
+                    as u64,
+            ),
+            ..Default::default()
+        });
+        self_.stats = stats.clone();
+
+        self_.start_instant = Some(Instant::now());
+        self_.elapsed = Duration::ZERO;
+        self_.paused_at = None;
+        self_.time_paused = Duration::ZERO;
+
+        let mut polling_interval = self_.polling_interval;
+        let mut sampling_interval = self_.sampling_interval;
+        let mut sleeping_interval = self_.sleeping_interval;
+        let mut adaptive = self_.adaptive;
+        let spin = self_.spin;
+        let single_probe = self_.single_probe;
+
+        let callbacks = self_.callbacks.clone();
+
+        let exit_signal = Arc::new(ExitSignal::default());
+        self_.exit_signal = exit_signal.clone();
+
+        // The monitoring thread reports its OS thread id as soon as it starts
+        let (send_thread_id, thread_id) = sync_channel(1);
+
+        let thread_name = format!("{}-monitor", self_.thread_name_prefix);
+        self_.callbacks.lock().stream_source = Some(exit_signal.clone());
+        // For the monitoring thread once the monitoring loop, taking the originals, ends
+        let (thread_stats, thread_callbacks) = (stats.clone(), callbacks.clone());
+        let handle = {
+            let monitor = move || {
+                // When callbacks last fired, to fire them at most every ``min_interval``
+                let mut callbacks_fired: Option<Instant> = None;
+                // Wall clock time anchored to the monotonic clock, so timestamps never go back
+                let clock = (SystemTime::now(), Instant::now());
+                let mut record = |sample: (Duration, Duration), polls: u64| {
+                    stats.accumulate(sample, polls);
+                    let metric = stats.contention_metric();
+                    let timestamp = (clock.0 + clock.1.elapsed())
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64();
+                    {
+                        let mut callbacks = callbacks.lock();
+                        callbacks.sample_queues.retain(|queue| !queue.is_closed());
+                        for queue in &callbacks.sample_queues {
+                            queue.push((timestamp, metric));
+                        }
+                    }
+                    if stats.shutting_down.load(Ordering::Acquire) {
+                        return;
+                    }
+                    // Streamed without holding the callbacks lock, as writing may block
+                    let stream_to = callbacks.lock().stream_due();
+                    if let Some(fd) = stream_to {
+                        let record = StreamRecord {
+                            t: timestamp,
+                            ratio: contention_ratio(sample.0, sample.1),
+                            metric,
+                        };
+                        let mut line = serde_json::to_vec(&record).unwrap_or_default();
+                        line.push(b'\n');
+                        if let Err(e) = write_fd(fd, &line) {
+                            {
+                                let mut callbacks = callbacks.lock();
+                                if matches!(&callbacks.stream, Some(stream) if stream.fd == fd) {
+                                    callbacks.stream = None;
+                                }
+                            }
+                            Python::with_gil(|py| {
+                                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                                let msg = format!(
+                                    "Error streaming to fd {}, streaming disabled: {}",
+                                    fd, e
+                                );
+                                PyErr::warn(py, warning, &msg, 0).unwrap_or_else(|e| e.print(py));
+                            });
+                        }
+                    }
+                    let (triggered, on_sample, on_observe, log_to) = {
+                        let mut callbacks = callbacks.lock();
+                        if callbacks.watchdog_tripped(metric) {
+                            // Same as receiving SIGINT, doesn't need the GIL
+                            unsafe { pyo3::ffi::PyErr_SetInterrupt() };
+                        }
+                        let due = callbacks_fired
+                            .is_none_or(|fired| fired.elapsed() >= callbacks.min_interval);
+                        if due {
+                            callbacks_fired = Some(Instant::now());
+                            (
+                                callbacks.triggered(metric),
+                                callbacks.sample.clone(),
+                                callbacks.otel.clone(),
+                                callbacks.log_due(),
+                            )
+                        } else {
+                            (vec![], None, None, callbacks.log_due())
+                        }
+                    };
+                    if let Some(callback) = on_sample {
+                        let args = (
+                            contention_ratio(sample.0, sample.1),
+                            sample.0.as_micros() as u64,
+                            sample.1.as_micros() as u64,
+                        );
+                        invoke_callback(&callback, args);
+                    }
+                    for callback in triggered {
+                        invoke_callback(&callback, (metric,));
+                    }
+                    if let Some(callback) = on_observe {
+                        let time_unix_nano = (clock.0 + clock.1.elapsed())
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_nanos() as u64;
+                        invoke_otel_callback(&callback, metric, time_unix_nano);
+                    }
+                    if let Some(logger_name) = log_to {
+                        log_contention(&logger_name, metric);
+                    }
+                };
+
+                let beat = || {
+                    if stats.shutting_down.load(Ordering::Acquire) {
+                        return;
+                    }
+                    let due = callbacks.lock().heartbeat_due();
+                    if let Some(callback) = due {
+                        invoke_callback(&callback, (stats.contention_metric(),));
+                    }
+                };
+
+                // Sampling, sleeping and control messages are interleaved on this one thread.
+                // Messages are checked between polls of a sampling window, and waited on while
+                // sleeping between windows.
+                let mut window_start = Instant::now();
+                // Time spent waiting for the GIL in this window, and the number of polls
+                // doing so, once polled at least once
+                let mut polled: Option<(Duration, u64)> = None;
+                let mut effective_polling =
+                    adaptive_polling_interval(polling_interval, adaptive, 0.0);
+                let mut paused = false;
+                loop {
+                    // Stopping, so exit without acquiring the GIL again, folding in any
+                    // partial sampling window as on receiving the stop itself
+                    if stats.shutting_down.load(Ordering::Acquire) {
+                        if let Some((time_waiting, polls)) = polled {
+                            record((time_waiting, window_start.elapsed()), polls);
+                        }
+                        break;
+                    }
+                    if !paused {
+                        beat();
+                    }
+                    let received = if paused {
+                        recv.recv().map_err(|_| RecvTimeoutError::Disconnected)
+                    } else if single_probe
+                        && polled.is_some()
+                        && window_start.elapsed() < sampling_interval
+                    {
+                        // Already probed this window, wait for it to end
+                        recv.recv_timeout(sampling_interval.saturating_sub(window_start.elapsed()))
+                    } else if window_start.elapsed() < sampling_interval {
+                        stats.sampling.store(true, Ordering::Relaxed);
+                        let start = Instant::now();
+                        let waited = Python::with_gil(move |_| start.elapsed());
+                        stats.record_latency(waited);
+                        let (time_waiting, polls) = polled.get_or_insert((Duration::ZERO, 0));
+                        *time_waiting += waited;
+                        *polls += 1;
+                        wait_between_polls(effective_polling, spin);
+                        recv.try_recv().map_err(|e| match e {
+                            TryRecvError::Empty => RecvTimeoutError::Timeout,
+                            TryRecvError::Disconnected => RecvTimeoutError::Disconnected,
+                        })
+                    } else {
+                        stats.sampling.store(false, Ordering::Relaxed);
+                        let (time_waiting, polls) = take(&mut polled).unwrap_or_default();
+                        let sample = (time_waiting, window_start.elapsed());
+                        // Waiting for the GIL can hold a window open well past its budget
+                        let overrun = sample
+                            .1
+                            .saturating_sub(sampling_interval + sleeping_interval);
+                        if !overrun.is_zero() {
+                            stats.overrun_count.fetch_add(1, Ordering::Relaxed);
+                            let on_overrun = callbacks.lock().overrun.clone();
+                            if let Some(callback) =
+                                on_overrun.filter(|_| !stats.shutting_down.load(Ordering::Acquire))
+                            {
+                                invoke_callback(&callback, (overrun.as_micros() as u64,));
+                            }
+                        }
+                        record(sample, polls);
+                        effective_polling = adaptive_polling_interval(
+                            polling_interval,
+                            adaptive,
+                            contention_ratio(sample.0, sample.1),
+                        );
+                        stats
+                            .effective_polling_micros
+                            .store(effective_polling.as_micros() as u64, Ordering::Relaxed);
+                        let sleep_start = Instant::now();
+                        let sleep_end = sleep_start + sleeping_interval;
+                        // Wake for any heartbeats due while sleeping
+                        let received = loop {
+                            let wake = match callbacks.lock().heartbeat_at() {
+                                Some(at) => at.min(sleep_end),
+                                None => sleep_end,
+                            };
+                            match recv.recv_timeout(wake.saturating_duration_since(Instant::now()))
+                            {
+                                Err(RecvTimeoutError::Timeout) if Instant::now() < sleep_end => {
+                                    beat()
+                                }
+                                received => break received,
+                            }
+                        };
+                        *stats.time_sleeping.write() += sleep_start.elapsed();
+                        window_start = Instant::now();
+                        received
+                    };
+                    match received {
+                        // Fold in a partial sampling window before exiting, whether stopped or
+                        // the knocker went away, so the final metric includes it.
+                        Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                            if let Some((time_waiting, polls)) = polled {
+                                record((time_waiting, window_start.elapsed()), polls);
+                            }
+                            break;
+                        }
+                        // A partial sampling window is discarded, starting afresh
+                        Ok(Message::Reset(generation)) => {
+                            let metric = stats.contention_metric();
+                            stats.reset();
+                            callbacks.lock().reset();
+                            polled = None;
+                            window_start = Instant::now();
+                            send.send(Ack(generation, metric)).unwrap(); // notify reset done
+                        }
+                        // Applied from the next poll
+                        Ok(Message::SetPolling(interval)) => {
+                            polling_interval = interval;
+                            if adaptive.is_none() {
+                                effective_polling = interval;
+                                stats
+                                    .effective_polling_micros
+                                    .store(interval.as_micros() as u64, Ordering::Relaxed);
+                            }
+                        }
+                        Ok(Message::SetAdaptive(bounds)) => {
+                            adaptive = bounds;
+                            effective_polling = adaptive_polling_interval(
+                                polling_interval,
+                                adaptive,
+                                stats.instant_contention_metric(),
+                            );
+                            stats
+                                .effective_polling_micros
+                                .store(effective_polling.as_micros() as u64, Ordering::Relaxed);
+                        }
+                        Ok(Message::SetSampling(interval)) => sampling_interval = interval,
+                        Ok(Message::SetSleeping(interval)) => sleeping_interval = interval,
+                        Ok(Message::Pause) => {
+                            stats.sampling.store(false, Ordering::Relaxed);
+                            paused = true;
+                            polled = None;
+                        }
+                        Ok(Message::Resume) => {
+                            paused = false;
+                            window_start = Instant::now();
+                        }
+                        Ok(Message::Panic) => panic!("Forced panic"),
+                        Err(RecvTimeoutError::Timeout) => (),
+                    }
+                }
+            };
+            thread::Builder::new()
+                .name(thread_name)
+                .spawn(move || {
+                    let _notify_on_exit = NotifyOnExit(exit_signal.clone());
+                    let _ = send_thread_id.send(native_thread_id());
+                    // Recorded as soon as it happens, rather than when the thread is joined
+                    if let Err(payload) = std::panic::catch_unwind(AssertUnwindSafe(monitor)) {
+                        thread_stats.set_error(payload);
+                    }
+                    thread_stats.sampling.store(false, Ordering::Relaxed);
+                    // Ends iteration of any streams once their remaining samples are consumed,
+                    // unless a newer monitoring thread has taken them over
+                    let mut callbacks = thread_callbacks.lock();
+                    let source = callbacks.stream_source.as_ref();
+                    if source.is_some_and(|source| Arc::ptr_eq(source, &exit_signal)) {
+                        callbacks.stream_source = None;
+                        for queue in callbacks.sample_queues.drain(..) {
+                            queue.close(false);
+                        }
+                    }
+                })
+                .inspect_err(|_| self_.callbacks.lock().stream_source = None)?
+        };
+        self_.handle = Some(handle);
+        self_.monitor_thread_id = thread_id.recv().ok().flatten();
+
+        if let Some(on_start) = self_.on_start.clone() {
+            // Release the knocker first, so the callback can use it
+            drop(slf);
+            invoke_callback(&on_start, ());
+        }
+        Ok(())
+    }
+
+    /// Make the monitoring thread panic, to test handling it. Kept in release builds, which
+    /// the tests run against, so the panic handling they exercise is what ships.
+    fn _panic_monitor(&self, py: Python) -> PyResult<()> {
+        self.send(py, Message::Panic)
     }
 
-    /// Reset the contention metric/monitoring state
-    pub fn reset_contention_metric(&mut self, py: Python) -> PyResult<()> {
-        if let Some(tx) = &self.tx {
-            // notify thread to reset metric and timers
-            if let Err(e) = tx.send(Message::Reset) {
-                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
-                PyErr::warn(py, warning, &e.to_string(), 0)?;
-            }
+    /// Reset to an unstarted state in the child after ``fork()``, where the monitoring
+    /// thread of the parent no longer exists. The metric reads 0 until started again.
+    fn _after_fork_in_child(&mut self) {
+        if let Some(handle) = take(&mut self.handle) {
+            // The thread doesn't exist in this process, so it can't be joined or detached.
+            std::mem::forget(handle);
+        }
+        self.tx = None;
+        self.rx = None;
+        self.stats = Default::default();
+        self.exit_signal = Default::default();
+        // The monitoring thread may have held the callbacks lock, so rebuild it, keeping
+        // the callbacks but ending any streams, which no thread will push to.
+        let mut callbacks = take(&mut *lock_after_fork(&self.callbacks));
+        callbacks.stream_source = None;
+        for queue in callbacks.sample_queues.drain(..) {
+            lock_after_fork(&queue.state).1 = true;
+        }
+        self.callbacks = Arc::new(Mutex::new(callbacks));
+        self.start_instant = None;
+        self.elapsed = Duration::ZERO;
+        self.paused_at = None;
+        self.time_paused = Duration::ZERO;
+    }
 
-            // wait for ack
-            if let Err(e) = self
-                .rx
-                .as_ref()
-                .unwrap() // if tx is set, then rx is as well.
-                .recv_timeout(self.timeout)
-            {
-                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
-                PyErr::warn(py, warning, &e.to_string(), 0)?;
-            }
+    /// Stop the monitoring thread if running, and start a fresh one with the same
+    /// intervals. Returns the contention metric of the new thread, which starts at 0.
+    fn restart(slf: PyRefMut<'_, Self>) -> PyResult<f64> {
+        let py = slf.py();
+        let knocker: Py<Self> = slf.into();
+        KnockKnock::start(knocker.as_ref(py), true)?;
+        let contention_metric = knocker.borrow(py).contention_metric();
+        Ok(contention_metric)
+    }
+
+    fn __repr__(&self) -> String {
+        let state = self.state();
+        format!(
+            "KnockKnock(polling={}us, sampling={}us, sleeping={}us, running={}, contention={})",
+            state.polling_interval_micros,
+            state.sampling_interval_micros,
+            state.sleeping_interval_micros,
+            if state.is_running { "True" } else { "False" },
+            state.contention_metric,
+        )
+    }
+
+    /// Compare the contention metric with a float, or another knocker's metric, for terse
+    /// alerting like ``if knocker > 0.5:``. The metric is read live, so comparing the same
+    /// running knocker twice may give different results. ``==`` and ``!=`` aren't by
+    /// metric, a knocker is only equal to itself.
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyObject {
+        let other = match other.extract::<PyRef<KnockKnock>>() {
+            Ok(knocker) => knocker.contention_metric(),
+            Err(_) => match other.extract::<f64>() {
+                Ok(threshold) => threshold,
+                Err(_) => return py.NotImplemented(),
+            },
+        };
+        let metric = self.contention_metric();
+        match op {
+            CompareOp::Lt => metric < other,
+            CompareOp::Le => metric <= other,
+            CompareOp::Gt => metric > other,
+            CompareOp::Ge => metric >= other,
+            CompareOp::Eq | CompareOp::Ne => return py.NotImplemented(),
+        }
+        .into_py(py)
+    }
+
+    /// Hash by identity, as knockers are only equal to themselves.
+    fn __hash__(slf: &PyCell<Self>) -> isize {
+        slf.as_ptr() as isize
+    }
+
+    /// Concise form for user-facing logs, e.g. ``GIL contention: 12% (running)``.
+    fn __str__(&self) -> String {
+        let state = if self.is_running() {
+            "running"
+        } else {
+            "stopped"
+        };
+        if self.gil_disabled {
+            return format!("GIL contention: n/a, GIL disabled ({})", state);
         }
-        *(*self.contention_metric).write() = 0f32;
+        format!(
+            "GIL contention: {:.0}% ({})",
+            self.contention_metric_pct(),
+            state
+        )
+    }
+
+    /// Configuration of the knocker for pickling, the running state isn't included.
+    fn __getstate__(&self) -> PickleState {
+        (
+            self.polling_interval.as_micros() as u64,
+            self.sampling_interval.as_micros() as u64,
+            self.sleeping_interval.as_micros() as u64,
+            self.timeout.as_micros() as u64,
+            self.ema_alpha,
+            self.window_secs(),
+            self.history_size,
+        )
+    }
+
+    /// Restore the configuration from ``__getstate__``, the knocker is left unstarted.
+    /// The state is validated as by the constructor, raising ``ValueError`` if invalid.
+    fn __setstate__(slf: &PyCell<Self>, state: PickleState) -> PyResult<()> {
+        let (polling, sampling, sleeping, timeout, ema_alpha, window_secs, history_size) = state;
+        let config = KnockKnock::__new__(
+            slf.py(),
+            Some(polling),
+            Some(sampling),
+            Some(sleeping),
+            Some(timeout),
+            ema_alpha,
+            window_secs,
+            Some(history_size),
+        )?;
+        KnockKnock::stop(slf)?;
+        let mut this = slf.borrow_mut();
+        this.polling_interval = config.polling_interval;
+        this.sampling_interval = config.sampling_interval;
+        this.sleeping_interval = config.sleeping_interval;
+        this.timeout = config.timeout;
+        this.ema_alpha = config.ema_alpha;
+        this.window = config.window;
+        this.history_size = config.history_size;
         Ok(())
     }
 
-    /// Start polling the GIL to check if it's locked.
-    fn start(mut slf: PyRefMut<'_, Self>) -> PyResult<()> {
-        unsafe {
-            if PyEval_ThreadsInitialized() == 0 {
-                PyEval_InitThreads();
-            }
+    /// Start monitoring when entering a ``with`` block.
+    fn __enter__(slf: PyRefMut<'_, Self>) -> PyResult<Py<Self>> {
+        let py = slf.py();
+        let knocker: Py<Self> = slf.into();
+        KnockKnock::start(knocker.as_ref(py), false)?;
+        Ok(knocker)
+    }
+
+    /// Stop monitoring when leaving a ``with`` block, any exception raised
+    /// within the block is propagated.
+    fn __exit__(
+        slf: &PyCell<Self>,
+        _exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        KnockKnock::stop(slf)?;
+        Ok(false)
+    }
+
+    /// Context manager failing with ``AssertionError`` if GIL contention within its
+    /// ``with`` block exceeded ``threshold``, going by the peak and final contention metric.
+    /// A running knocker has its metric reset on entering the block, otherwise it's started
+    /// on entering and stopped on leaving the block.
+    ///
+    /// ```python
+    /// with knocker.assert_below(0.2):
+    ///     run_workload()
+    /// ```
+    fn assert_below(slf: PyRef<'_, Self>, threshold: f64) -> ContentionAssertion {
+        ContentionAssertion {
+            knocker: slf.into(),
+            threshold,
+            started: false,
         }
+    }
 
-        // Register atexit function to stop gilknocker thread
-        // which reduces the chance of odd 'no Python frame' core dumps
-        // when trying to acquire the GIL when the process has exited.
-        {
-            let ptr = slf.as_ptr();
-            let py = slf.py();
-            let __knocker = unsafe { PyObject::from_borrowed_ptr(py, ptr) };
-            let atexit = py.import("atexit")?;
-            let locals = pyo3::types::PyDict::new(py);
-            locals.set_item("__knocker", __knocker)?;
-            locals.set_item("atexit", atexit)?;
-            py.run("atexit.register(__knocker.stop)", None, Some(locals))?;
+    /// Context manager measuring GIL contention within its ``with`` block, readable from
+    /// its ``contention_metric`` once the block exits, e.g. for repeated measurements
+    /// without restarting the monitoring thread. As with ``assert_below()``, a running
+    /// knocker has its metric reset on entering the block and keeps running, otherwise
+    /// it's started on entering and stopped on leaving the block.
+    ///
+    /// ```python
+    /// for batch in batches:
+    ///     with knocker.measuring() as m:
+    ///         process(batch)
+    ///     print(m.contention_metric)
+    /// ```
+    fn measuring(slf: PyRef<'_, Self>) -> Measurement {
+        Measurement {
+            knocker: slf.into(),
+            contention_metric: None,
+            started: false,
         }
+    }
 
-        let self_: &mut KnockKnock = slf.deref_mut();
+    /// Prefix of the monitoring thread's name, ``"<prefix>-monitor"``, as seen by profilers
+    /// and debuggers. Defaults to ``"gilknocker"``, changes apply from the next ``start()``.
+    /// Linux truncates thread names to 15 bytes.
+    #[getter]
+    pub fn thread_name_prefix(&self) -> String {
+        self.thread_name_prefix.clone()
+    }
 
-        // send messages to thread
-        let (tx, recv) = channel();
-        self_.tx = Some(tx);
+    /// Set ``thread_name_prefix``, which can't contain null bytes.
+    #[setter]
+    pub fn set_thread_name_prefix(&mut self, prefix: String) -> PyResult<()> {
+        if prefix.contains('\0') {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "thread_name_prefix can't contain null bytes",
+            ));
+        }
+        self.thread_name_prefix = prefix;
+        Ok(())
+    }
 
-        // recieve messages from thread
-        let (send, rx) = channel();
-        self_.rx = Some(rx);
+    /// Whether to busy-wait between polls, rather than sleep, for polling intervals below
+    /// 1ms, which the OS may otherwise sleep well past. Polls then keep to the polling
+    /// interval more precisely, at the cost of the monitoring thread keeping a CPU core
+    /// busy throughout each sampling window. Defaults to ``False``, changes apply from the
+    /// next ``start()``.
+    #[getter]
+    pub fn spin(&self) -> bool {
+        self.spin
+    }
+
+    /// Set ``spin``
+    #[setter]
+    pub fn set_spin(&mut self, spin: bool) {
+        self.spin = spin;
+    }
 
-        let contention_metric = Arc::new(const_rwlock(0_f32));
-        self_.contention_metric = contention_metric.clone();
+    /// Whether each sampling window acquires the GIL just once, at its start, taking
+    /// the time that took over the window's length as its contention ratio, rather than
+    /// polling throughout. This cuts overhead to one acquisition per window, for casual
+    /// monitoring, but a single probe may land in a lull or a burst of contention, so each
+    /// sample is far noisier and only the average over many is meaningful. Defaults to
+    /// ``False``, changes apply from the next ``start()``.
+    #[getter]
+    pub fn single_probe(&self) -> bool {
+        self.single_probe
+    }
 
-        let polling_interval = self_.polling_interval;
-        let sampling_interval = self_.sampling_interval;
-        let sleeping_interval = self_.sleeping_interval;
+    /// Set ``single_probe``
+    #[setter]
+    pub fn set_single_probe(&mut self, single_probe: bool) {
+        self.single_probe = single_probe;
+    }
 
-        let handle = {
-            thread::spawn(move || {
-                let mut total_time_waiting = Duration::from_millis(0);
-                let mut total_time_sampling = Duration::from_millis(0);
+    /// OS thread id of the monitoring thread, as in ``threading.get_native_id()``, e.g. to
+    /// pick it out of a ``py-spy dump`` or ``/proc/self/task``. ``None`` when not running, or
+    /// on platforms other than Linux and macOS.
+    #[getter]
+    pub fn monitor_thread_id(&self) -> Option<u64> {
+        self.monitor_thread_id.filter(|_| self.is_running())
+    }
+
+    /// Message of a panic in the monitoring thread since last started, if any.
+    /// When set, ``stop`` raises ``RuntimeError`` with it.
+    #[getter]
+    pub fn error(&self) -> Option<String> {
+        self.stats.error.lock().clone()
+    }
+
+    /// Is a sampling window open, rather than the monitoring thread sleeping between
+    /// windows, paused or stopped? E.g. to wait for a quiescent point before reading.
+    #[getter]
+    pub fn is_sampling(&self) -> bool {
+        self.stats.sampling.load(Ordering::Relaxed)
+    }
+
+    /// Is the GIL knocker thread running?
+    #[getter]
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some() && !self.exit_signal.has_exited()
+    }
+
+    /// Tell the monitoring thread to stop, without waiting for it to exit, e.g. to avoid
+    /// blocking an asyncio event loop. Await ``await_stopped()`` for it to exit, the final
+    /// contention metric can then be read, and ``stop()`` returns it without blocking.
+    pub fn stop_nowait(&mut self, py: Python) -> PyResult<()> {
+        self.send_stop(py)
+    }
+
+    /// An asyncio future, resolving once the monitoring thread has exited after
+    /// ``stop_nowait()``, to ``True``, or to ``False`` if it didn't within the timeout.
+    /// The wait happens in the event loop's default executor, rather than blocking the loop.
+    pub fn await_stopped<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let waiter = StopWaiter {
+            exit_signal: self.handle.as_ref().map(|_| self.exit_signal.clone()),
+            timeout: self.timeout,
+        };
+        py.import("asyncio")?
+            .call_method0("get_running_loop")?
+            .call_method1("run_in_executor", (py.None(), Py::new(py, waiter)?))
+    }
 
-                let sample_gil = || {
+    /// Stop polling the GIL, returning the final contention metric which
+    /// includes any partial sampling window at the time of stopping. The monitoring
+    /// thread checks for the stop between polls, so this waits for at most one poll.
+    pub fn stop(slf: &PyCell<Self>) -> PyResult<f64> {
+        let py = slf.py();
+        let stopping = {
+            let mut this = slf.borrow_mut();
+            this.send_stop(py)?;
+            take(&mut this.handle).map(|handle| (handle, this.exit_signal.clone(), this.timeout))
+        };
+        if let Some((handle, exit_signal, timeout)) = stopping {
+            // Release the GIL while waiting, the monitoring thread may be polling it, and
+            // the knocker, so other threads can still read it meanwhile.
+            let finished = py.allow_threads(move || exit_signal.wait(timeout));
+            let this = slf.borrow();
+            if !finished {
+                if let Some(on_final) = this.on_final.clone() {
+                    // Still fire it once the thread does exit
+                    let (stats, round_digits) = (this.stats.clone(), this.round_digits);
+                    let elapsed_secs = this.elapsed.as_secs_f64();
                     thread::spawn(move || {
-                        let time_sampling = Instant::now();
-                        let mut time_waiting = Duration::from_secs(0);
-
-                        // Begin polling gil for duration of sampling interval
-                        while time_sampling.elapsed() < sampling_interval {
-                            let start = Instant::now();
-                            time_waiting += Python::with_gil(move |_| start.elapsed());
-                            thread::sleep(polling_interval);
+                        if handle.join().is_ok() {
+                            let metric = stats.contention_metric();
+                            let metric =
+                                round_digits.map_or(metric, |d| round_half_even(metric, d));
+                            let peak = *stats.peak_contention_metric.read();
+                            let num_samples = stats.num_samples.load(Ordering::Relaxed);
+                            Python::with_gil(|py| {
+                                let summary = (metric, peak, num_samples, elapsed_secs);
+                                invoke_final_callback(py, &on_final, summary)
+                            });
                         }
-                        (time_waiting, time_sampling.elapsed())
-                    })
-                };
+                    });
+                }
+                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                PyErr::warn(py, warning, "Timed out waiting for monitoring thread.", 0)?;
+                return Ok(this.contention_metric());
+            }
+            if let Err(payload) = handle.join() {
+                this.stats.set_error(payload);
+            }
+            let summary = (
+                this.contention_metric(),
+                this.peak_contention_metric(),
+                this.num_samples(),
+                this.elapsed_secs(),
+            );
+            let (on_stop, on_final) = (this.on_stop.clone(), this.on_final.clone());
+            // Callbacks may use the knocker themselves
+            drop(this);
+            if let Some(on_stop) = &on_stop {
+                invoke_callback(on_stop, ());
+            }
+            if let Some(on_final) = &on_final {
+                invoke_final_callback(py, on_final, summary);
+            }
+        }
+        let this = slf.borrow();
+        match this.error() {
+            Some(msg) => Err(pyo3::exceptions::PyRuntimeError::new_err(format!(
+                "Monitoring thread panicked: {msg}"
+            ))),
+            None => Ok(this.contention_metric()),
+        }
+    }
+}
 
-                let mut handle = Some(sample_gil());
-                loop {
-                    match recv.recv_timeout(sleeping_interval) {
-                        Ok(message) => match message {
-                            Message::Stop => break,
-                            Message::Reset => {
-                                total_time_waiting = Duration::from_millis(0);
-                                total_time_sampling = Duration::from_millis(0);
-                                *(*contention_metric).write() = 0_f32;
-                                send.send(Ack).unwrap(); // notify reset done
-                            }
-                        },
-                        Err(RecvTimeoutError::Disconnected) => break,
-                        Err(RecvTimeoutError::Timeout) => {
-                            if handle
-                                .as_ref()
-                                .map(|hdl| hdl.is_finished())
-                                .unwrap_or_else(|| false)
-                            {
-                                let (time_waiting, time_sampling) =
-                                    take(&mut handle).unwrap().join().unwrap();
-                                total_time_sampling += time_sampling;
-                                total_time_waiting += time_waiting;
-                                let mut cm = (*contention_metric).write();
-                                *cm = total_time_waiting.as_micros() as f32
-                                    / total_time_sampling.as_micros() as f32;
-                                debug_assert!(handle.is_none()); // handle reset when done
-                            } else if handle.is_none() {
-                                handle = Some(sample_gil());
-                            }
+/// Accessors for Rust code embedding a knocker, e.g. another extension module holding a
+/// ``KnockKnock`` it started, which read the metrics shared with the monitoring thread
+/// directly, without going through Python or needing the GIL.
+impl KnockKnock {
+    /// Current contention metric, unrounded, or -1 if the GIL was found disabled.
+    pub fn current_metric(&self) -> f64 {
+        if self.gil_disabled {
+            return -1_f64;
+        }
+        self.stats.contention_metric()
+    }
+
+    /// Cumulative ``(time_waiting, time_sampling)`` since starting or the last reset.
+    pub fn raw_totals(&self) -> (Duration, Duration) {
+        *self.stats.totals.read()
+    }
+}
+
+impl KnockKnock {
+    /// Reset the metrics, returning whether the reset took, see
+    /// ``reset_contention_metric()``, and the contention metric just before it.
+    fn reset(slf: &PyCell<Self>) -> PyResult<(bool, f64)> {
+        let py = slf.py();
+        let mut this = slf.borrow_mut();
+        let mut acked = None;
+        if let Some(tx) = this.tx.clone() {
+            // if tx is set, then rx is as well, unless another reset is waiting on it.
+            let Some(rx) = take(&mut this.rx) else {
+                return Err(pyo3::exceptions::PyRuntimeError::new_err(
+                    "Already resetting from another thread",
+                ));
+            };
+            // notify thread to reset metric and timers
+            this.generation += 1;
+            let generation = this.generation;
+            if let Err(e) = tx.send(Message::Reset(generation)) {
+                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                PyErr::warn(py, warning, &e.to_string(), 0)?;
+            }
+
+            // wait for ack of this generation, skipping any late acks of previous resets,
+            // without borrowing the knocker, so other threads can still read it meanwhile.
+            let timeout = this.timeout;
+            drop(this);
+            let (rx, result) = py.allow_threads(move || {
+                let start = Instant::now();
+                let result = loop {
+                    match rx.recv_timeout(timeout.saturating_sub(start.elapsed())) {
+                        Ok(Ack(acked_generation, metric)) if acked_generation == generation => {
+                            break Ok(metric)
                         }
+                        Ok(_) => continue,
+                        Err(e) => break Err(e),
                     }
+                };
+                (rx, result)
+            });
+            this = slf.borrow_mut();
+            this.rx = Some(rx);
+            match result {
+                Ok(metric) => acked = Some(metric),
+                Err(e) => {
+                    let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                    PyErr::warn(py, warning, &e.to_string(), 0)?;
                 }
-            })
+            }
+        }
+        let metric = match acked {
+            Some(metric) => metric,
+            None => {
+                let metric = this.stats.contention_metric();
+                this.stats.reset();
+                metric
+            }
         };
-        self_.handle = Some(handle);
+        if this.start_instant.is_some() {
+            this.start_instant = Some(Instant::now());
+        }
+        this.elapsed = Duration::ZERO;
+        this.time_paused = Duration::ZERO;
+        if this.paused_at.is_some() {
+            this.paused_at = Some(Instant::now());
+        }
+        Ok((acked.is_some() || this.tx.is_none(), metric))
+    }
+
+    /// Freeze the elapsed time and tell the monitoring thread to stop, if not already told.
+    fn send_stop(&mut self, py: Python) -> PyResult<()> {
+        self.stats.shutting_down.store(true, Ordering::Release);
+        self.elapsed = self.monitored();
+        self.start_instant = None;
+        self.paused_at = None;
+        self.time_paused = Duration::ZERO;
+        if let Some(send) = take(&mut self.tx) {
+            // Fails if the monitoring thread already exited, having panicked
+            if let Err(e) = send.send(Message::Stop) {
+                if !self.exit_signal.has_exited() {
+                    let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                    PyErr::warn(py, warning, &e.to_string(), 0)?;
+                }
+            }
+        }
         Ok(())
     }
 
-    /// Is the GIL knocker thread running?
-    #[getter]
-    pub fn is_running(&self) -> bool {
-        self.handle.is_some()
+    /// Round a presented metric to ``round_digits``, if set.
+    fn round(&self, value: f64) -> f64 {
+        match self.round_digits {
+            Some(digits) => round_half_even(value, digits),
+            None => value,
+        }
+    }
+
+    /// Time spent monitoring since starting or the last reset, excluding time paused.
+    fn monitored(&self) -> Duration {
+        match self.start_instant {
+            Some(instant) => instant
+                .elapsed()
+                .saturating_sub(self.time_paused)
+                .saturating_sub(self.paused_at.map(|at| at.elapsed()).unwrap_or_default()),
+            None => self.elapsed,
+        }
+    }
+
+    /// Send a message to the monitoring thread if running, warning if it has gone away.
+    fn send(&self, py: Python, message: Message) -> PyResult<()> {
+        if let Some(tx) = &self.tx {
+            if let Err(e) = tx.send(message) {
+                let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
+                PyErr::warn(py, warning, &e.to_string(), 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Snapshot of the current contention metric and configuration.
+    fn state(&self) -> State {
+        State {
+            contention_metric: self.contention_metric(),
+            polling_interval_micros: self.polling_interval.as_micros() as u64,
+            sampling_interval_micros: self.sampling_interval.as_micros() as u64,
+            sleeping_interval_micros: self.sleeping_interval.as_micros() as u64,
+            timeout_micros: self.timeout.as_micros() as u64,
+            is_running: self.is_running(),
+        }
     }
+}
 
-    /// Stop polling the GIL.
-    pub fn stop(&mut self, py: Python) -> PyResult<()> {
+impl Drop for KnockKnock {
+    /// Stop the monitoring thread if the knocker is garbage collected while running,
+    /// by disconnecting from it. If it doesn't exit within the timeout, it's simply detached.
+    fn drop(&mut self) {
+        self.stats.shutting_down.store(true, Ordering::Release);
         if let Some(handle) = take(&mut self.handle) {
-            if let Some(send) = take(&mut self.tx) {
-                if let Err(e) = send.send(Message::Stop) {
-                    let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
-                    PyErr::warn(py, warning, &e.to_string(), 0)?;
+            if take(&mut self.tx).is_some() {
+                // Release the GIL while waiting, the monitoring thread may be polling it.
+                let timeout = self.timeout;
+                let exit_signal = self.exit_signal.clone();
+                let finished =
+                    Python::with_gil(|py| py.allow_threads(move || exit_signal.wait(timeout)));
+                if finished {
+                    handle.join().ok();
                 }
+            }
+        }
+    }
+}
 
-                let start = Instant::now();
-                while !handle.is_finished() {
-                    if start.elapsed() > self.timeout {
-                        let warning = py.get_type::<pyo3::exceptions::PyUserWarning>();
-                        PyErr::warn(py, warning, "Timed out waiting for sampling thread.", 0)?;
-                        return Ok(());
-                    }
-                    thread::sleep(Duration::from_millis(100));
-                }
+/// Callable waiting for the monitoring thread to exit, run in an executor by
+/// ``KnockKnock.await_stopped()``.
+#[pyclass(name = "StopWaiter", module = "gilknocker")]
+pub struct StopWaiter {
+    /// Signal of the monitoring thread, if there is one to wait for.
+    exit_signal: Option<Arc<ExitSignal>>,
+    timeout: Duration,
+}
+
+#[pymethods]
+impl StopWaiter {
+    fn __call__(&self, py: Python) -> bool {
+        match &self.exit_signal {
+            Some(exit_signal) => {
+                let (exit_signal, timeout) = (exit_signal.clone(), self.timeout);
+                py.allow_threads(move || exit_signal.wait(timeout))
             }
-            handle.join().ok(); // Just ignore any potential panic from sampling thread.
+            None => true,
         }
-        Ok(())
+    }
+}
+
+/// Asynchronous iterator of samples returned by ``KnockKnock.stream()``.
+#[pyclass(name = "SampleStream", module = "gilknocker")]
+pub struct SampleStream {
+    queue: Arc<SampleQueue>,
+}
+
+#[pymethods]
+impl SampleStream {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Waits for the next sample in the event loop's default executor, rather than
+    /// blocking the loop.
+    fn __anext__(&self, py: Python) -> PyResult<Option<PyObject>> {
+        let waiter = SampleWaiter {
+            queue: self.queue.clone(),
+        };
+        let future = py
+            .import("asyncio")?
+            .call_method0("get_running_loop")?
+            .call_method1("run_in_executor", (py.None(), Py::new(py, waiter)?))?;
+        Ok(Some(future.into()))
+    }
+
+    /// Stop receiving samples, discarding any not yet consumed.
+    pub fn close(&self) {
+        self.queue.close(true);
+    }
+
+    /// Whether the stream has ended, closed or the monitoring thread having exited.
+    #[getter]
+    pub fn closed(&self) -> bool {
+        self.queue.is_closed()
+    }
+}
+
+/// Callable waiting for the next sample of a ``SampleStream``, run in an executor.
+#[pyclass(name = "SampleWaiter", module = "gilknocker")]
+pub struct SampleWaiter {
+    queue: Arc<SampleQueue>,
+}
+
+#[pymethods]
+impl SampleWaiter {
+    fn __call__(&self, py: Python) -> PyResult<(f64, f64)> {
+        let queue = self.queue.clone();
+        py.allow_threads(move || queue.next())
+            .ok_or_else(|| pyo3::exceptions::PyStopAsyncIteration::new_err(()))
+    }
+}
+
+/// Context manager returned by ``KnockKnock.assert_below()``.
+#[pyclass(name = "ContentionAssertion", module = "gilknocker")]
+pub struct ContentionAssertion {
+    knocker: Py<KnockKnock>,
+    threshold: f64,
+    /// Whether the knocker was started on entering, so should be stopped on leaving.
+    started: bool,
+}
+
+#[pymethods]
+impl ContentionAssertion {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        let py = slf.py();
+        if slf.knocker.borrow(py).is_running() {
+            KnockKnock::reset_contention_metric(slf.knocker.as_ref(py))?;
+        } else {
+            KnockKnock::start(slf.knocker.as_ref(py), false)?;
+            slf.started = true;
+        }
+        Ok(slf)
+    }
+
+    /// Any exception raised within the block is propagated instead of asserting.
+    fn __exit__(
+        &mut self,
+        py: Python,
+        exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        if take(&mut self.started) {
+            KnockKnock::stop(self.knocker.as_ref(py))?;
+        }
+        let knocker = self.knocker.borrow(py);
+        let metric = knocker
+            .peak_contention_metric()
+            .max(knocker.contention_metric());
+        if exc_type.is_none() && metric > self.threshold {
+            return Err(pyo3::exceptions::PyAssertionError::new_err(format!(
+                "GIL contention of {:.4} exceeded threshold of {}",
+                metric, self.threshold
+            )));
+        }
+        Ok(false)
+    }
+}
+
+/// Context manager returned by ``KnockKnock.measuring()``.
+#[pyclass(name = "Measurement", module = "gilknocker")]
+pub struct Measurement {
+    knocker: Py<KnockKnock>,
+    /// Contention metric accumulated within the block, ``None`` until it exits.
+    #[pyo3(get)]
+    contention_metric: Option<f64>,
+    /// Whether the knocker was started on entering, so should be stopped on leaving.
+    started: bool,
+}
+
+#[pymethods]
+impl Measurement {
+    fn __enter__(mut slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        let py = slf.py();
+        slf.contention_metric = None;
+        if slf.knocker.borrow(py).is_running() {
+            KnockKnock::reset_contention_metric(slf.knocker.as_ref(py))?;
+        } else {
+            KnockKnock::start(slf.knocker.as_ref(py), false)?;
+            slf.started = true;
+        }
+        Ok(slf)
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python,
+        _exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        if take(&mut self.started) {
+            KnockKnock::stop(self.knocker.as_ref(py))?;
+        }
+        let knocker = self.knocker.borrow(py);
+        self.contention_metric = Some(knocker.contention_metric());
+        Ok(false)
+    }
+}
+
+/// Decorator measuring GIL contention of each call to a function, taking the same
+/// configuration as the ``KnockKnock`` constructor. Calls to the decorated function
+/// return ``(result, contention_metric)``, each call monitored by its own ``KnockKnock``,
+/// so nested decorated calls are measured independently.
+///
+/// Example
+/// -------
+/// ```python
+/// from gilknocker import knock
+///
+/// @knock(polling_interval_micros=1_000)
+/// def smart_code():
+///     ...
+///
+/// result, contention_metric = smart_code()
+/// ```
+#[pyfunction]
+#[pyo3(signature = (
+    polling_interval_micros = None,
+    sampling_interval_micros = None,
+    sleeping_interval_micros = None,
+    timeout_micros = None,
+    ema_alpha = None,
+    window_secs = None,
+    history_size = None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn knock(
+    py: Python,
+    polling_interval_micros: Option<u64>,
+    sampling_interval_micros: Option<u64>,
+    sleeping_interval_micros: Option<u64>,
+    timeout_micros: Option<u64>,
+    ema_alpha: Option<f64>,
+    window_secs: Option<f64>,
+    history_size: Option<usize>,
+) -> PyResult<KnockDecorator> {
+    let config = KnockKnockBuilder {
+        polling_interval_micros,
+        sampling_interval_micros,
+        sleeping_interval_micros,
+        timeout_micros,
+        ema_alpha,
+        window_secs,
+        history_size,
+    };
+    config.build(py)?; // Fail when decorating rather than on the first call
+    Ok(KnockDecorator {
+        config: Arc::new(config),
+    })
+}
+
+/// Decorator returned by ``knock()``.
+#[pyclass(name = "KnockDecorator", module = "gilknocker")]
+pub struct KnockDecorator {
+    config: Arc<KnockKnockBuilder>,
+}
+
+#[pymethods]
+impl KnockDecorator {
+    fn __call__(&self, py: Python, func: PyObject) -> PyResult<Py<KnockedFunction>> {
+        let wrapper = Py::new(
+            py,
+            KnockedFunction {
+                func: func.clone_ref(py),
+                config: self.config.clone(),
+            },
+        )?;
+        py.import("functools")?
+            .call_method1("update_wrapper", (wrapper.clone_ref(py), func))?;
+        Ok(wrapper)
+    }
+}
+
+/// Function decorated with ``knock()``, returning ``(result, contention_metric)`` when called.
+#[pyclass(name = "KnockedFunction", module = "gilknocker", dict)]
+pub struct KnockedFunction {
+    func: PyObject,
+    config: Arc<KnockKnockBuilder>,
+}
+
+#[pymethods]
+impl KnockedFunction {
+    #[pyo3(signature = (*args, **kwargs))]
+    fn __call__(
+        &self,
+        py: Python,
+        args: &pyo3::types::PyTuple,
+        kwargs: Option<&pyo3::types::PyDict>,
+    ) -> PyResult<(PyObject, f64)> {
+        let knocker = Py::new(py, self.config.build(py)?)?;
+        KnockKnock::start(knocker.as_ref(py), false)?;
+        let result = self.func.call(py, args, kwargs);
+        let metric = KnockKnock::stop(knocker.as_ref(py))?;
+        Ok((result?, metric))
+    }
+
+    /// Bind to ``obj`` when accessed as an attribute, so decorated methods receive ``self``.
+    fn __get__(
+        slf: PyRef<'_, Self>,
+        obj: Option<PyObject>,
+        _objtype: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let py = slf.py();
+        match obj {
+            Some(obj) if !obj.is_none(py) => Ok(py
+                .import("types")?
+                .getattr("MethodType")?
+                .call1((slf, obj))?
+                .into()),
+            _ => Ok(slf.into_py(py)),
+        }
+    }
+}
+
+/// Process-wide knocker shared by ``default()``, ``start_default()`` and ``stop_default()``.
+static DEFAULT_KNOCKER: pyo3::sync::GILOnceCell<Py<KnockKnock>> = pyo3::sync::GILOnceCell::new();
+
+/// Process-wide shared ``KnockKnock`` with the default config, created on first use, for
+/// scripts which don't want to pass a knocker around. Every call returns the same knocker.
+///
+/// Safe to use from any thread, as creating and accessing it is serialized by the GIL.
+/// As with any ``KnockKnock``, using it while another thread is stopping it raises
+/// ``RuntimeError``.
+#[pyfunction(name = "default")]
+fn default_knocker(py: Python) -> PyResult<Py<KnockKnock>> {
+    if let Some(knocker) = DEFAULT_KNOCKER.get(py) {
+        return Ok(knocker.clone_ref(py));
+    }
+    let knocker = Py::new(
+        py,
+        KnockKnock::__new__(py, None, None, None, None, None, None, None)?,
+    )?;
+    // Creating it may have let another thread create one first, keep whichever was first
+    let _ = DEFAULT_KNOCKER.set(py, knocker);
+    Ok(DEFAULT_KNOCKER
+        .get(py)
+        .expect("default knocker was just set")
+        .clone_ref(py))
+}
+
+/// Start the ``default()`` knocker, if not already running, and return it.
+#[pyfunction]
+fn start_default(py: Python) -> PyResult<Py<KnockKnock>> {
+    let knocker = default_knocker(py)?;
+    if !knocker.borrow(py).is_running() {
+        KnockKnock::start(knocker.as_ref(py), false)?;
+    }
+    Ok(knocker)
+}
+
+/// Stop the ``default()`` knocker, returning its final contention metric.
+#[pyfunction]
+fn stop_default(py: Python) -> PyResult<f64> {
+    KnockKnock::stop(default_knocker(py)?.as_ref(py))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn contention_ratio_of_waiting_to_sampling() {
+        assert_eq!(contention_ratio(ms(25), ms(100)), 0.25);
+        assert_eq!(contention_ratio(Duration::ZERO, ms(100)), 0.0);
+        assert_eq!(contention_ratio(ms(100), ms(100)), 1.0);
+    }
+
+    #[test]
+    fn contention_ratio_without_sampling_or_overshooting() {
+        // No sampling time is no contention, not NaN
+        assert_eq!(contention_ratio(ms(5), Duration::ZERO), 0.0);
+        assert_eq!(contention_ratio(Duration::ZERO, Duration::ZERO), 0.0);
+        // Waits slightly exceeding the time sampled clamp to full contention
+        assert_eq!(contention_ratio(ms(101), ms(100)), 1.0);
+    }
+
+    #[test]
+    fn accumulate_updates_totals_and_metrics() {
+        let stats = Stats::default();
+        stats.accumulate((ms(10), ms(100)), 3);
+        stats.accumulate((ms(50), ms(100)), 2);
+
+        assert_eq!(*stats.totals.read(), (ms(60), ms(200)));
+        assert_eq!(stats.num_samples.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.last_polls.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.total_polls.load(Ordering::Relaxed), 5);
+        assert_eq!(stats.contention_metric(), 0.3);
+        assert_eq!(stats.instant_contention_metric(), 0.5);
+        assert_eq!(*stats.peak_contention_metric.read(), 0.3);
+
+        let histogram = stats.histogram.lock();
+        assert_eq!(histogram.len(), HISTOGRAM_BINS);
+        assert_eq!(histogram.iter().sum::<u64>(), 2);
+        assert_eq!(histogram[HISTOGRAM_BINS / 10], 1);
+        assert_eq!(histogram[HISTOGRAM_BINS / 2], 1);
+    }
+
+    #[test]
+    fn raw_totals_reads_accumulated_totals() {
+        let knocker = KnockKnock::default();
+        assert_eq!(knocker.raw_totals(), (Duration::ZERO, Duration::ZERO));
+
+        knocker.stats.accumulate((ms(20), ms(80)), 1);
+        assert_eq!(knocker.raw_totals(), (ms(20), ms(80)));
+        assert_eq!(knocker.current_metric(), 0.25);
+    }
+
+    #[test]
+    fn contention_metric_pct_clamps_overshoot() {
+        let knocker = KnockKnock::default();
+        knocker.stats.accumulate((ms(101), ms(100)), 1);
+        assert_eq!(knocker.contention_metric_pct(), 100.0);
+
+        // Even were the raw metric itself to overshoot
+        knocker
+            .stats
+            .contention_metric
+            .store(1.02_f64.to_bits(), Ordering::Relaxed);
+        assert_eq!(knocker.contention_metric_pct(), 100.0);
     }
 }
@@ -1,9 +1,21 @@
+import asyncio
+import atexit
+import contextlib
+import gc
+import json
+import math
+import os
+import pickle
 import random
+import subprocess
+import sys
 import pytest
 import numpy as np
 import threading
 import time
-from gilknocker import KnockKnock
+import warnings
+import gilknocker
+from gilknocker import KnockKnock, MergeableStats, Snapshot, knock
 
 
 N_THREADS = 4
@@ -16,6 +28,26 @@ def a_lotta_gil():
         pass
 
 
+@contextlib.contextmanager
+def contention(n_threads=N_THREADS):
+    """Keep the GIL busy from ``n_threads`` threads until the block exits"""
+    stop = threading.Event()
+
+    def busy():
+        while not stop.is_set():
+            pass
+
+    threads = [threading.Thread(target=busy, daemon=True) for _ in range(n_threads)]
+    for thread in threads:
+        thread.start()
+    try:
+        yield
+    finally:
+        stop.set()
+        for thread in threads:
+            thread.join()
+
+
 def a_little_gil():
     """Work which releases the GIL"""
     for i in range(5):
@@ -94,6 +126,2440 @@ def test_knockknock_reset_contention_metric():
         knocker.stop()
 
 
+def test_knockknock_reset_under_contention():
+    knocker = KnockKnock(
+        polling_interval_micros=10,
+        sampling_interval_micros=1_000,
+        sleeping_interval_micros=100_000,
+    )
+    knocker.start()
+    threads = [
+        threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+    ]
+    try:
+        for thread in threads:
+            thread.start()
+        for _ in range(100):
+            knocker.reset_contention_metric()
+            assert knocker.contention_metric == 0.0
+            assert knocker.num_samples == 0
+            time.sleep(0.01)
+    finally:
+        knocker.stop()
+        for thread in threads:
+            thread.join()
+
+
+def test_knockknock_stop_latency():
+    knocker = KnockKnock(
+        polling_interval_micros=1000, sleeping_interval_micros=1_000_000
+    )
+    knocker.start()
+    time.sleep(0.1)
+
+    start = time.monotonic()
+    knocker.stop()
+    # no outstanding sample to wait for, so stop shouldn't wait on a coarse sleep
+    assert time.monotonic() - start < 0.05
+
+
+def test_knockknock_start_stop_tight_loop_never_warns():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        for _ in range(50):
+            knocker.start()
+            knocker.stop()
+            assert not knocker.is_running
+    assert not caught
+
+
+def test_knockknock_context_manager():
+    with KnockKnock(polling_interval_micros=1000) as knocker:
+        assert knocker.is_running
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+    assert not knocker.is_running
+    assert knocker.contention_metric > 0.6
+
+
+def test_knockknock_context_manager_propagates_exception():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    with pytest.raises(ValueError):
+        with knocker:
+            raise ValueError("boom")
+    assert not knocker.is_running
+
+
+def test_knockknock_restart():
+    knocker = _run(a_lotta_gil)
+    try:
+        assert knocker.contention_metric > 0.6
+        assert knocker.restart() == 0.0
+        assert knocker.is_running
+        assert knocker.contention_metric < 0.001
+    finally:
+        knocker.stop()
+
+    # restarting a stopped knocker just starts it
+    assert knocker.restart() == 0.0
+    assert knocker.is_running
+    knocker.stop()
+
+
+def test_knockknock_stop_returns_final_metric():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.start()
+    threads = [
+        threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+    ]
+    for thread in threads:
+        thread.start()
+
+    time.sleep(1)
+    metric = knocker.stop()
+    assert metric > 0.0
+    assert metric == knocker.contention_metric
+
+    for thread in threads:
+        thread.join()
+
+
+def test_knockknock_totals():
+    knocker = _run(a_lotta_gil)
+    metric = knocker.stop()
+
+    assert knocker.total_time_sampling_micros > 0
+    assert knocker.total_time_waiting_micros <= knocker.total_time_sampling_micros
+    ratio = knocker.total_time_waiting_micros / knocker.total_time_sampling_micros
+    assert ratio == pytest.approx(metric, rel=1e-3)
+
+
+def test_knockknock_num_samples():
+    # 1ms sampling + 10ms sleeping per sample
+    knocker = KnockKnock(polling_interval_micros=100)
+    assert knocker.num_samples == 0
+    knocker.start()
+    try:
+        time.sleep(0.5)
+        assert knocker.num_samples > 0
+
+        knocker.reset_contention_metric()
+        assert knocker.num_samples == 0
+    finally:
+        knocker.stop()
+
+    knocker.start()
+    try:
+        assert knocker.num_samples == 0
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_elapsed_secs():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert knocker.elapsed_secs == 0.0
+    knocker.start()
+    try:
+        prev = knocker.elapsed_secs
+        for _ in range(5):
+            time.sleep(0.01)
+            assert knocker.elapsed_secs > prev
+            prev = knocker.elapsed_secs
+
+        knocker.reset_contention_metric()
+        assert knocker.elapsed_secs < prev
+    finally:
+        knocker.stop()
+
+    frozen = knocker.elapsed_secs
+    time.sleep(0.01)
+    assert knocker.elapsed_secs == frozen
+
+
+def test_knockknock_peak_contention_metric():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert knocker.peak_contention_metric == 0.0
+    knocker.start()
+    try:
+        assert knocker.peak_contention_metric == 0.0  # no samples yet, not NaN
+
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+        # idle, letting the metric cool down
+        for _ in range(5):
+            a_little_gil()
+
+        assert knocker.peak_contention_metric > 0.0
+        assert knocker.peak_contention_metric >= knocker.contention_metric
+
+        knocker.reset_contention_metric()
+        assert knocker.peak_contention_metric == 0.0
+    finally:
+        knocker.stop()
+
+
+@pytest.mark.parametrize("alpha", (0.0, -0.1, 1.1))
+def test_knockknock_ema_alpha_invalid(alpha):
+    with pytest.raises(ValueError):
+        KnockKnock(ema_alpha=alpha)
+
+
+def test_knockknock_ema_tracks_step_change():
+    cumulative = KnockKnock(polling_interval_micros=1000)
+    ema = KnockKnock(polling_interval_micros=1000, ema_alpha=0.5)
+    assert cumulative.ema_alpha is None
+    assert ema.ema_alpha == 0.5
+
+    cumulative.start()
+    ema.start()
+    try:
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+        # step down to work which releases the GIL
+        for _ in range(5):
+            a_little_gil()
+
+        assert ema.contention_metric < cumulative.contention_metric
+
+        ema.reset_contention_metric()
+        assert ema.contention_metric == 0.0
+    finally:
+        cumulative.stop()
+        ema.stop()
+
+
+@pytest.mark.parametrize("window_secs", (0.0, -1.0, float("inf")))
+def test_knockknock_window_secs_invalid(window_secs):
+    with pytest.raises(ValueError):
+        KnockKnock(window_secs=window_secs)
+
+
+def test_knockknock_window_secs_with_ema_invalid():
+    with pytest.raises(ValueError):
+        KnockKnock(ema_alpha=0.5, window_secs=1.0)
+
+
+def test_knockknock_sliding_window_decays():
+    knocker = KnockKnock(polling_interval_micros=1000, window_secs=0.5)
+    assert knocker.window_secs == 0.5
+    knocker.start()
+    try:
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        assert knocker.contention_metric > 0.6
+
+        # idle for longer than the window
+        time.sleep(1.5)
+        assert knocker.contention_metric < 0.2
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_history():
+    # 1ms sampling + 10ms sleeping per sample
+    knocker = KnockKnock(polling_interval_micros=100, history_size=5)
+    assert knocker.history() == []
+    knocker.start()
+    try:
+        while knocker.num_samples <= 5:
+            time.sleep(0.01)
+        history = knocker.history()
+        assert len(history) == 5
+        assert all(0.0 <= ratio <= 1.0 for ratio in history)
+
+        # oldest dropped, newest appended at the end
+        n_samples = knocker.num_samples
+        while knocker.num_samples == n_samples:
+            time.sleep(0.001)
+        assert knocker.history()[:-1] == history[1:]
+
+        knocker.reset_contention_metric()
+        assert len(knocker.history()) < 5
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_history_with_timestamps():
+    # 1ms sampling + 10ms sleeping per sample
+    knocker = KnockKnock(polling_interval_micros=100)
+    knocker.start()
+    try:
+        while knocker.num_samples < 5:
+            time.sleep(0.01)
+        history = knocker.history_with_timestamps()
+        assert all(0.0 <= ratio <= 1.0 for _, ratio in history)
+
+        timestamps = [timestamp for timestamp, _ in history]
+        assert all(a < b for a, b in zip(timestamps, timestamps[1:]))
+        for a, b in zip(timestamps, timestamps[1:]):
+            assert 0.011 <= b - a < 0.1
+
+        knocker.reset_contention_metric()
+        while knocker.num_samples < 1:
+            time.sleep(0.01)
+        assert knocker.history_with_timestamps()[0][0] < timestamps[-1]
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_metric_precision():
+    knocker = _run(a_lotta_gil)
+    metric = knocker.stop()
+
+    # metric is calculated in double precision from the same microsecond totals
+    ratio = knocker.total_time_waiting_micros / knocker.total_time_sampling_micros
+    assert metric == pytest.approx(ratio, rel=1e-12)
+
+
+def test_knockknock_concurrent_reads():
+    knocker = KnockKnock(polling_interval_micros=100)
+    knocker.start()
+    stop = threading.Event()
+
+    def read_metric():
+        while not stop.is_set():
+            assert 0.0 <= knocker.contention_metric <= 1.0
+
+    readers = [threading.Thread(target=read_metric, daemon=True) for _ in range(4)]
+    try:
+        for reader in readers:
+            reader.start()
+        time.sleep(1)
+        assert knocker.num_samples > 0
+    finally:
+        stop.set()
+        for reader in readers:
+            reader.join(timeout=5)
+            assert not reader.is_alive()
+        knocker.stop()
+
+
+def test_knockknock_to_dict():
+    knocker = KnockKnock(1_000, 10_000, 100_000, 200_000)
+    assert knocker.to_dict() == {
+        "contention_metric": 0.0,
+        "polling_interval_micros": 1_000,
+        "sampling_interval_micros": 10_000,
+        "sleeping_interval_micros": 100_000,
+        "timeout_micros": 200_000,
+        "is_running": False,
+    }
+
+    knocker.start()
+    try:
+        snapshot = knocker.to_dict()
+        assert snapshot["is_running"] is True
+        assert isinstance(snapshot["contention_metric"], float)
+        for key in (
+            "polling_interval_micros",
+            "sampling_interval_micros",
+            "sleeping_interval_micros",
+            "timeout_micros",
+        ):
+            assert isinstance(snapshot[key], int)
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_to_json():
+    knocker = _run(a_lotta_gil)
+    metric = knocker.stop()
+
+    state = json.loads(knocker.to_json())
+    assert state.keys() == knocker.to_dict().keys()
+    assert state["contention_metric"] == pytest.approx(metric)
+    assert state["polling_interval_micros"] == 1_000
+    assert state["is_running"] is False
+
+
+@pytest.mark.parametrize("namespace", (None, "myapp"))
+def test_knockknock_prometheus(namespace):
+    knocker = _run(a_lotta_gil)
+    metric = knocker.stop()
+
+    prefix = f"{namespace}_" if namespace else ""
+    gauges = {}
+    for line in knocker.prometheus(namespace).splitlines():
+        if line.startswith("# HELP "):
+            continue
+        elif line.startswith("# TYPE "):
+            _, _, name, kind = line.split(" ")
+            assert name.startswith(prefix)
+            assert kind == "gauge"
+        else:
+            name, value = line.split(" ")
+            gauges[name] = float(value)
+
+    assert gauges[f"{prefix}gil_contention_ratio"] == pytest.approx(metric)
+    assert gauges[f"{prefix}gil_polling_interval_micros"] == 1_000
+
+
+def test_knockknock_pickle():
+    knocker = KnockKnock(1_000, 20_000, 300_000, 400_000, ema_alpha=0.25)
+    unpickled = pickle.loads(pickle.dumps(knocker))
+    assert unpickled.to_dict() == knocker.to_dict()
+    assert unpickled.ema_alpha == 0.25
+    assert unpickled.window_secs is None
+
+    knocker.start()
+    try:
+        unpickled = pickle.loads(pickle.dumps(knocker))
+        assert not unpickled.is_running
+        assert unpickled.contention_metric == 0.0
+    finally:
+        knocker.stop()
+
+
+class _PickledKnocker:
+    """Pickles as a ``KnockKnock`` restored from the given ``__setstate__`` state"""
+
+    def __init__(self, state):
+        self.state = state
+
+    def __reduce__(self):
+        return KnockKnock, (), self.state
+
+
+@pytest.mark.parametrize(
+    "state",
+    [
+        (0, 10_000, 100_000, 200_000, None, None, 1024),
+        (1_000, 10_000, 100_000, 200_000, 2.0, None, 1024),
+        (1_000, 10_000, 100_000, 200_000, 0.5, 1.0, 1024),
+    ],
+)
+def test_knockknock_unpickle_invalid_state(state):
+    with pytest.raises(ValueError):
+        pickle.loads(pickle.dumps(_PickledKnocker(state)))
+
+
+def test_knockknock_repr():
+    knocker = KnockKnock(1_000, 10_000, 100_000)
+    assert repr(knocker) == (
+        "KnockKnock(polling=1000us, sampling=10000us, sleeping=100000us, "
+        "running=False, contention=0)"
+    )
+
+    knocker.start()
+    try:
+        assert "running=True" in repr(knocker)
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_str():
+    knocker = KnockKnock(1_000, 10_000, 100_000)
+    assert str(knocker) == "GIL contention: 0% (stopped)"
+    assert str(knocker) != repr(knocker)
+
+    knocker.start()
+    try:
+        assert "%" in str(knocker)
+        assert str(knocker).endswith("(running)")
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_snapshot():
+    knocker = _run(a_lotta_gil)
+    try:
+        snapshot = knocker.snapshot()
+        assert isinstance(snapshot, Snapshot)
+        assert snapshot.num_samples > 0
+        assert snapshot.peak_contention_metric >= snapshot.contention_metric > 0.0
+        assert snapshot.elapsed_secs > 0.0
+
+        fields = (
+            snapshot.contention_metric,
+            snapshot.peak_contention_metric,
+            snapshot.num_samples,
+            snapshot.elapsed_secs,
+        )
+        knocker.reset_contention_metric()
+        assert fields == (
+            snapshot.contention_metric,
+            snapshot.peak_contention_metric,
+            snapshot.num_samples,
+            snapshot.elapsed_secs,
+        )
+
+        with pytest.raises(AttributeError):
+            snapshot.contention_metric = 1.0
+    finally:
+        knocker.stop()
+
+
+def test_snapshot_diff():
+    earlier = Snapshot(
+        total_time_waiting_micros=1_000, total_time_sampling_micros=10_000
+    )
+    later = Snapshot(total_time_waiting_micros=6_000, total_time_sampling_micros=20_000)
+    assert later.diff(earlier) == {
+        "delta_time_waiting_micros": 5_000,
+        "delta_time_sampling_micros": 10_000,
+        "contention_metric": 0.5,
+    }
+
+    # no sampling in between
+    assert later.diff(later)["contention_metric"] == 0.0
+
+
+def test_knockknock_snapshot_diff():
+    knocker = _run(a_lotta_gil)
+    try:
+        earlier = knocker.snapshot()
+        while knocker.num_samples == earlier.num_samples:
+            time.sleep(0.01)
+        diff = knocker.snapshot().diff(earlier)
+        assert diff["delta_time_sampling_micros"] > 0
+        assert 0.0 <= diff["contention_metric"] <= 1.0
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_threshold_callback():
+    rising, falling = [], []
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.set_threshold_callback(0.1, rising.append, on_fall=falling.append)
+    knocker.start()
+    try:
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+        # fired once on crossing, not for every sample above the threshold
+        assert len(rising) == 1
+        assert rising[0] > 0.1
+
+        knocker.reset_contention_metric()
+        time.sleep(0.5)
+        assert len(falling) == 1
+        assert falling[0] <= 0.1
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_threshold_callback_exception_warns():
+    def callback(metric):
+        raise ValueError("boom")
+
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.set_threshold_callback(-1.0, callback)
+    with pytest.warns(UserWarning, match="boom"):
+        knocker.start()
+        time.sleep(0.5)
+        knocker.stop()
+
+
+def test_knockknock_sample_callback():
+    samples = []
+    knocker = KnockKnock(polling_interval_micros=100)
+    knocker.set_sample_callback(lambda *sample: samples.append(sample))
+    knocker.start()
+    try:
+        time.sleep(0.5)
+        knocker.set_sample_callback(None)
+        time.sleep(0.05)  # let any in-flight callback finish
+        n_samples = len(samples)
+        time.sleep(0.1)
+        assert len(samples) == n_samples
+    finally:
+        knocker.stop()
+
+    assert n_samples > 1
+    for ratio, time_waiting_micros, time_sampling_micros in samples:
+        assert 0.0 <= ratio <= 1.0
+        assert time_waiting_micros <= time_sampling_micros
+
+
+def test_knockknock_hysteresis_invalid():
+    knocker = KnockKnock()
+    with pytest.raises(ValueError):
+        knocker.set_hysteresis(0.1, 0.5, print, print)
+
+
+def test_knockknock_hysteresis():
+    entered, exited = [], []
+    knocker = KnockKnock(polling_interval_micros=1000, ema_alpha=0.5)
+    knocker.set_hysteresis(0.5, 0.1, entered.append, exited.append)
+    knocker.start()
+    try:
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+
+        # cool back down
+        for _ in range(10):
+            a_little_gil()
+    finally:
+        knocker.stop()
+
+    assert len(entered) == 1
+    assert entered[0] > 0.5
+    assert len(exited) == 1
+    assert exited[0] < 0.1
+
+
+def test_knockknock_set_polling_interval():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert knocker.polling_interval_micros == 1000
+    knocker.start()
+    try:
+        time.sleep(0.2)
+        n_samples = knocker.num_samples
+        knocker.set_polling_interval_micros(100)
+        assert knocker.polling_interval_micros == 100
+
+        # accumulated stats are kept, and sampling continues
+        assert knocker.num_samples >= n_samples
+        time.sleep(0.5)
+        assert knocker.num_samples > n_samples
+    finally:
+        knocker.stop()
+
+    knocker.set_polling_interval_micros(500)
+    assert knocker.polling_interval_micros == 500
+
+
+def test_knockknock_set_sampling_interval():
+    knocker = KnockKnock(polling_interval_micros=100)
+    assert knocker.sampling_interval_micros == 1_000
+    knocker.start()
+    try:
+        knocker.set_sampling_interval_micros(50_000)
+        assert knocker.sampling_interval_micros == 50_000
+        time.sleep(0.5)
+        knocker.reset_contention_metric()
+        while knocker.num_samples == 0:
+            time.sleep(0.01)
+        # longer windows are observable in the sampling time of each sample
+        assert knocker.total_time_sampling_micros >= 50_000
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_set_sleeping_interval():
+    # 1ms sampling + 10ms sleeping per sample
+    knocker = KnockKnock(polling_interval_micros=100)
+    assert knocker.sleeping_interval_micros == 10_000
+    knocker.start()
+    try:
+        time.sleep(0.5)
+        fast = knocker.num_samples
+
+        knocker.set_sleeping_interval_micros(100_000)
+        assert knocker.sleeping_interval_micros == 100_000
+        time.sleep(0.2)  # let the current cycle finish
+        knocker.reset_contention_metric()
+        time.sleep(0.5)
+        slow = knocker.num_samples
+        assert slow < fast / 2
+    finally:
+        knocker.stop()
+
+
+@pytest.mark.parametrize(
+    "kwargs",
+    (
+        dict(polling_interval_micros=0),
+        dict(sampling_interval_micros=0),
+        dict(sleeping_interval_micros=0),
+    ),
+)
+def test_knockknock_zero_interval_invalid(kwargs):
+    with pytest.raises(ValueError, match=list(kwargs)[0]):
+        KnockKnock(**kwargs)
+
+
+def test_knockknock_polling_greater_than_sampling_warns():
+    with pytest.warns(UserWarning, match="at most once per sampling window"):
+        KnockKnock(polling_interval_micros=10_000, sampling_interval_micros=1_000)
+
+
+def test_knockknock_set_zero_interval_invalid():
+    knocker = KnockKnock()
+    for setter in (
+        knocker.set_polling_interval_micros,
+        knocker.set_sampling_interval_micros,
+        knocker.set_sleeping_interval_micros,
+    ):
+        with pytest.raises(ValueError):
+            setter(0)
+
+
+def test_knockknock_builder():
+    knocker = (
+        KnockKnock.builder()
+        .with_polling_micros(1_000)
+        .with_sampling_micros(20_000)
+        .with_sleeping_micros(300_000)
+        .with_timeout_micros(400_000)
+        .build()
+    )
+    assert knocker.to_dict() == KnockKnock(1_000, 20_000, 300_000, 400_000).to_dict()
+    assert KnockKnock.builder().build().to_dict() == KnockKnock().to_dict()
+
+    builder = KnockKnock.builder().with_ema_alpha(0.5).with_history_size(10)
+    assert builder.build().ema_alpha == 0.5
+
+
+def test_knockknock_builder_validates():
+    with pytest.raises(ValueError):
+        KnockKnock.builder().with_polling_micros(0).build()
+    with pytest.raises(ValueError):
+        KnockKnock.builder().with_ema_alpha(0.5).with_window_secs(1.0).build()
+
+
+def test_knockknock_presets():
+    low_overhead = KnockKnock.low_overhead().to_dict()
+    high_accuracy = KnockKnock.high_accuracy().to_dict()
+    assert low_overhead != high_accuracy
+
+    def sleeping_to_sampling(config):
+        return config["sleeping_interval_micros"] / config["sampling_interval_micros"]
+
+    assert sleeping_to_sampling(low_overhead) > sleeping_to_sampling(high_accuracy)
+    assert (
+        high_accuracy["polling_interval_micros"]
+        < low_overhead["polling_interval_micros"]
+    )
+
+    for knocker in (KnockKnock.low_overhead(), KnockKnock.high_accuracy()):
+        knocker.start()
+        knocker.stop()
+
+
+def test_knockknock_contention_metric_pct():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert knocker.contention_metric_pct == 0.0
+
+    knocker = _run(a_lotta_gil)
+    metric = knocker.stop()
+    assert 0.0 < knocker.contention_metric_pct <= 100.0
+    assert knocker.contention_metric_pct == pytest.approx(min(metric * 100, 100.0))
+
+
+def test_knockknock_metric_is_bounded():
+    # aggressive polling, where each poll's wait may overshoot the sampling time
+    knocker = KnockKnock(polling_interval_micros=1, sampling_interval_micros=1_000)
+    knocker.start()
+    try:
+        assert knocker.contention_metric == 0.0  # no samples yet
+        threads = [
+            threading.Thread(target=a_lotta_gil, daemon=True) for _ in range(N_THREADS)
+        ]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+    finally:
+        knocker.stop()
+
+    assert 0.0 <= knocker.contention_metric <= 1.0
+    assert all(0.0 <= ratio <= 1.0 for ratio in knocker.history())
+    assert 0.0 <= knocker.peak_contention_metric <= 1.0
+
+
+def test_knockknock_start_stop_immediately_not_nan():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.start()
+    metric = knocker.stop()
+    assert not math.isnan(metric)
+    assert 0.0 <= metric <= 1.0
+
+    knocker.start()
+    knocker.reset_contention_metric()
+    assert knocker.contention_metric == 0.0
+    assert knocker.stop() == knocker.contention_metric
+    assert not math.isnan(knocker.contention_metric)
+
+
+def _n_os_threads():
+    return len(os.listdir("/proc/self/task"))
+
+
+@pytest.mark.skipif(not sys.platform.startswith("linux"), reason="Requires /proc")
+def test_knockknock_drop_stops_thread():
+    baseline = _n_os_threads()
+
+    def scope():
+        knocker = KnockKnock(polling_interval_micros=1000)
+        knocker.start()
+        assert _n_os_threads() > baseline
+
+    scope()
+    gc.collect()
+    time.sleep(0.1)  # give the monitoring thread time to exit
+    assert _n_os_threads() == baseline
+
+
+def test_knockknock_start_twice_raises():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.start()
+    try:
+        with pytest.raises(RuntimeError):
+            knocker.start()
+        assert knocker.is_running
+    finally:
+        knocker.stop()
+
+
+@pytest.mark.skipif(not sys.platform.startswith("linux"), reason="Requires /proc")
+def test_knockknock_start_force_does_not_leak_thread():
+    baseline = _n_os_threads()
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.start()
+    try:
+        for _ in range(5):
+            knocker.start(force=True)
+        time.sleep(0.1)
+        # just the one monitoring thread
+        assert _n_os_threads() == baseline + 1
+        assert knocker.is_running
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_no_error_when_sampling_succeeds():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert knocker.error is None
+    knocker.start()
+    time.sleep(0.1)
+    knocker.stop()  # doesn't raise
+    assert knocker.error is None
+
+
+def test_knockknock_drop_accounts_for_last_sample():
+    samples = []
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=200_000,
+        sleeping_interval_micros=1000,
+    )
+    knocker.set_sample_callback(lambda *sample: samples.append(sample))
+    knocker.start()
+    time.sleep(0.05)  # mid first sampling window
+    assert not samples
+
+    # Dropping disconnects the monitoring thread, which folds in the partial window
+    del knocker
+    gc.collect()
+    assert len(samples) == 1
+
+
+@pytest.mark.skipif(not hasattr(os, "fork"), reason="Requires fork")
+def test_knockknock_fork_resets_in_child():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    # Keep the monitoring thread taking the callbacks lock while forking
+    knocker.set_heartbeat(0.001, lambda metric: None)
+    knocker.start()
+    stream = knocker.stream()
+    try:
+        time.sleep(0.1)
+        with warnings.catch_warnings():
+            warnings.simplefilter("ignore", DeprecationWarning)  # forking with threads
+            pid = os.fork()
+        if pid == 0:
+            code = 1
+            try:
+                ok = not knocker.is_running and knocker.contention_metric == 0
+                ok = ok and stream.closed
+                knocker.set_heartbeat(1.0, None)
+                knocker.start()
+                ok = ok and knocker.is_running
+                knocker.stop()
+                code = 0 if ok else 1
+            finally:
+                os._exit(code)
+        _, status = os.waitpid(pid, 0)
+        assert os.WIFEXITED(status) and os.WEXITSTATUS(status) == 0
+        assert knocker.is_running
+        assert not stream.closed
+    finally:
+        stream.close()
+        knocker.stop()
+
+
+def test_knockknock_is_gil_disabled_matches_interpreter():
+    gil_disabled = hasattr(sys, "_is_gil_enabled") and not sys._is_gil_enabled()
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert not knocker.is_gil_disabled
+
+    with warnings.catch_warnings():
+        warnings.simplefilter("ignore")
+        knocker.start()
+    try:
+        assert knocker.is_gil_disabled == gil_disabled
+        if gil_disabled:
+            assert not knocker.is_running
+            assert knocker.contention_metric == -1.0
+        else:
+            assert knocker.is_running
+    finally:
+        knocker.stop()
+
+
+@pytest.mark.skipif(not sys.platform.startswith("linux"), reason="Requires /proc")
+def test_knockknock_reuses_sampling_thread():
+    baseline = set(os.listdir("/proc/self/task"))
+    knocker = KnockKnock(
+        polling_interval_micros=100,
+        sampling_interval_micros=1000,
+        sleeping_interval_micros=1000,
+    )
+    knocker.start()
+    seen = set()
+    try:
+        end = time.time() + 0.5
+        while time.time() < end:
+            seen.update(os.listdir("/proc/self/task"))
+            time.sleep(0.001)
+        assert knocker.num_samples > 10
+    finally:
+        knocker.stop()
+
+    # Only the monitoring thread, not one per sampling window
+    assert len(seen - baseline) <= 1
+
+
+def test_knockknock_stop_latency_within_sampling_window():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=1_000_000,
+        sleeping_interval_micros=1000,
+    )
+    knocker.start()
+    time.sleep(0.1)  # mid sampling window
+
+    start = time.monotonic()
+    knocker.stop()
+    # stop is checked between polls, rather than after the sampling window
+    assert time.monotonic() - start < 0.5
+    assert knocker.num_samples == 1  # the partial window
+
+
+def test_knockknock_measure():
+    def measure():
+        return KnockKnock.measure(
+            1.0, polling_interval_micros=1000, sleeping_interval_micros=10_000
+        )
+
+    with contention():
+        busy = measure()
+    assert busy > measure()
+
+    with pytest.raises(ValueError):
+        KnockKnock.measure(-1.0)
+
+
+def test_knock_decorator():
+    @knock(polling_interval_micros=1000, sleeping_interval_micros=10_000)
+    def busy(n):
+        """Spin for a while"""
+        for _ in range(n):
+            pass
+        return n
+
+    @knock(polling_interval_micros=1000)
+    def outer():
+        return busy(1_000_000)
+
+    result, metric = busy(n=1_000_000)
+    assert result == 1_000_000
+    assert 0.0 <= metric <= 1.0
+    assert busy.__name__ == "busy"
+    assert busy.__doc__ == "Spin for a while"
+
+    # nested calls are monitored independently
+    (inner_result, inner_metric), outer_metric = outer()
+    assert inner_result == 1_000_000
+    assert 0.0 <= inner_metric <= 1.0
+    assert 0.0 <= outer_metric <= 1.0
+
+    with pytest.raises(ValueError):
+        knock(polling_interval_micros=0)
+
+
+def test_knock_decorator_method():
+    class Worker:
+        def __init__(self, n):
+            self.n = n
+
+        @knock(polling_interval_micros=1000)
+        def busy(self, scale=1):
+            for _ in range(self.n * scale):
+                pass
+            return self.n * scale
+
+        @staticmethod
+        @knock(polling_interval_micros=1000)
+        def static(n):
+            return n
+
+    worker = Worker(1_000)
+    result, metric = worker.busy(scale=2)
+    assert result == 2_000
+    assert 0.0 <= metric <= 1.0
+    assert Worker.busy(worker)[0] == 1_000
+    assert worker.static(3)[0] == 3
+
+
+def test_knockknock_assert_below():
+    knocker = KnockKnock(polling_interval_micros=1000, sleeping_interval_micros=10_000)
+
+    with knocker.assert_below(0.5):
+        time.sleep(0.5)
+    assert not knocker.is_running  # started and stopped by the block
+
+    with pytest.raises(AssertionError):
+        with knocker.assert_below(0.1):
+            end = time.time() + 1
+            while time.time() < end:
+                pass
+
+    # a running knocker is reset on entering, and left running
+    knocker.start()
+    try:
+        with knocker.assert_below(0.5):
+            time.sleep(0.5)
+        assert knocker.is_running
+    finally:
+        knocker.stop()
+
+    # exceptions within the block propagate
+    with pytest.raises(KeyError):
+        with knocker.assert_below(0.0):
+            raise KeyError()
+
+
+def test_knockknock_logging(caplog):
+    knocker = KnockKnock(polling_interval_micros=1000, sleeping_interval_micros=10_000)
+    knocker.set_logging(0.1, logger_name="gilknocker.test")
+    with caplog.at_level("INFO", logger="gilknocker.test"):
+        with knocker:
+            time.sleep(0.5)
+    records = [r for r in caplog.records if r.name == "gilknocker.test"]
+    assert records
+    assert all("GIL contention" in r.getMessage() for r in records)
+
+    with pytest.raises(ValueError):
+        knocker.set_logging(-1.0)
+    knocker.set_logging(None)
+
+
+def test_knockknock_min_callback_interval():
+    calls = []
+    knocker = KnockKnock(
+        polling_interval_micros=100,
+        sampling_interval_micros=500,
+        sleeping_interval_micros=500,
+    )
+    assert knocker.min_callback_interval_micros == 0
+    knocker.set_min_callback_interval_micros(100_000)
+    assert knocker.min_callback_interval_micros == 100_000
+    knocker.set_sample_callback(lambda *sample: calls.append(sample))
+    with knocker:
+        time.sleep(0.5)
+
+    assert knocker.num_samples > 50
+    assert 0 < len(calls) <= 7  # at most every 100ms
+
+
+def test_knockknock_instant_contention_metric():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.instant_contention_metric == 0.0
+    knocker.start()
+    try:
+        with contention():
+            time.sleep(1)
+        assert knocker.instant_contention_metric > 0.2
+
+        # idle, the last sample has next to no contention, the cumulative metric
+        # remains elevated by the burst
+        time.sleep(0.5)
+        assert knocker.instant_contention_metric < 0.1
+        assert knocker.contention_metric > knocker.instant_contention_metric
+
+        knocker.reset_contention_metric()
+        assert knocker.instant_contention_metric == 0.0
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_samples_over_threshold():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    knocker.set_count_threshold(0.5)
+    knocker.start()
+    try:
+        # a few bursts of contention, with idle in between
+        for _ in range(3):
+            with contention(1):
+                time.sleep(0.2)
+            time.sleep(0.2)
+    finally:
+        knocker.stop()
+
+    over = sum(ratio > 0.5 for ratio in knocker.history())
+    assert over > 0
+    assert knocker.samples_over_threshold == over
+    assert knocker.samples_over_threshold < knocker.num_samples
+
+    knocker.reset_contention_metric()
+    assert knocker.samples_over_threshold == 0
+
+    with pytest.raises(ValueError):
+        knocker.set_count_threshold(math.nan)
+
+
+def test_knockknock_histogram():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.histogram(10) == [0] * 10
+    knocker.start()
+    try:
+        # a mix of contention, then idle
+        with contention(1):
+            time.sleep(0.3)
+        time.sleep(0.3)
+    finally:
+        knocker.stop()
+
+    histogram = knocker.histogram(10)
+    assert len(histogram) == 10
+    assert sum(histogram) == knocker.num_samples
+    assert histogram[0] > 0  # idle samples
+    assert knocker.histogram(1) == [knocker.num_samples]
+
+    # Bins of 0.001 straddling an edge, as at 1/3, are counted in the lower bucket
+    bins = knocker.histogram(1000)
+    assert sum(bins) == knocker.num_samples
+    thirds = [sum(bins[:334]), sum(bins[334:667]), sum(bins[667:])]
+    assert knocker.histogram(3) == thirds
+
+    knocker.reset_contention_metric()
+    assert knocker.histogram(10) == [0] * 10
+
+    with pytest.raises(ValueError):
+        knocker.histogram(0)
+    with pytest.raises(ValueError, match="between 1 and 1000"):
+        knocker.histogram(2000)
+
+
+def test_knockknock_duty_cycle():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=2000,
+        sleeping_interval_micros=50_000,
+    )
+    assert knocker.duty_cycle == 0.0
+    with knocker:
+        time.sleep(0.5)
+    assert 0.0 < knocker.duty_cycle < 0.2
+
+    knocker.reset_contention_metric()
+    assert knocker.duty_cycle == 0.0
+
+
+def test_knockknock_write_csv(tmp_path):
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+        history_size=5,
+    )
+    with knocker:
+        time.sleep(0.3)
+
+    path = tmp_path / "history.csv"
+    knocker.write_csv(path)
+    lines = path.read_text().splitlines()
+    assert lines[0] == "timestamp_secs,ratio,time_waiting_micros,time_sampling_micros"
+    rows = [line.split(",") for line in lines[1:]]
+    assert len(rows) == min(knocker.num_samples, 5)
+    assert [float(row[1]) for row in rows] == knocker.history()
+
+    with pytest.raises(OSError):
+        knocker.write_csv(tmp_path / "missing" / "history.csv")
+
+
+def test_knockknock_history_numpy():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert len(knocker.history_numpy()) == 0
+    with knocker:
+        time.sleep(0.3)
+
+    ratios = knocker.history_numpy()
+    assert isinstance(ratios, np.ndarray)
+    assert ratios.dtype == np.float64
+    assert len(ratios) == len(knocker.history())
+    assert ratios.tolist() == knocker.history()
+
+
+@pytest.mark.skipif(not sys.platform.startswith("linux"), reason="Requires /proc")
+def test_knockknock_thread_name():
+    def thread_names():
+        names = []
+        for task in os.listdir("/proc/self/task"):
+            with open(f"/proc/self/task/{task}/comm") as f:
+                names.append(f.read().strip())
+        return names
+
+    knocker = KnockKnock(polling_interval_micros=1000)
+    assert knocker.thread_name_prefix == "gilknocker"
+    with knocker:
+        assert "gilknocker-moni" in thread_names()  # truncated to 15 bytes
+
+    knocker.thread_name_prefix = "gk-test"
+    with knocker:
+        assert "gk-test-monitor" in thread_names()
+
+    with pytest.raises(ValueError):
+        knocker.thread_name_prefix = "gk\0test"
+
+
+def test_knockknock_multiple_instances_are_independent():
+    first = KnockKnock(polling_interval_micros=1000, sleeping_interval_micros=10_000)
+    second = KnockKnock(polling_interval_micros=1000, sleeping_interval_micros=10_000)
+    first.start()
+    second.start()
+    try:
+        with contention():
+            time.sleep(0.5)
+
+            assert first.contention_metric > 0.2
+            assert second.contention_metric > 0.2
+
+            # resetting or stopping one leaves the other alone
+            first.reset_contention_metric()
+            assert second.num_samples > 0
+            assert second.contention_metric > 0.2
+            first.stop()
+            assert second.is_running
+            num_samples = second.num_samples
+            time.sleep(0.2)
+            assert second.num_samples > num_samples
+    finally:
+        first.stop()
+        second.stop()
+
+
+def test_knockknock_pause_resume():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    knocker.pause()  # not running, nothing to pause
+    assert not knocker.is_paused
+
+    knocker.start()
+    try:
+        time.sleep(0.2)
+        knocker.pause()
+        assert knocker.is_paused
+        time.sleep(0.05)  # let the monitoring thread see the pause
+        num_samples = knocker.num_samples
+        elapsed = knocker.elapsed_secs
+        time.sleep(0.5)
+        assert knocker.num_samples == num_samples
+        assert knocker.elapsed_secs == pytest.approx(elapsed, abs=0.01)
+
+        knocker.resume()
+        assert not knocker.is_paused
+        time.sleep(0.2)
+        assert knocker.num_samples > num_samples
+        assert knocker.elapsed_secs < 0.6  # excludes the pause
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_watchdog():
+    knocker = KnockKnock(polling_interval_micros=1000, sleeping_interval_micros=10_000)
+    knocker.set_watchdog(0.2, duration_secs=0.2)
+    knocker.start()
+    try:
+        with contention(), pytest.raises(KeyboardInterrupt):
+            end = time.time() + 10
+            while time.time() < end:
+                time.sleep(0.01)
+    finally:
+        knocker.stop()
+
+    with pytest.raises(ValueError):
+        knocker.set_watchdog(0.2, duration_secs=-1)
+    knocker.set_watchdog(None)
+
+
+def test_knockknock_otel_observe():
+    points = []
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    knocker.otel_observe(points.append)
+    before = time.time_ns()
+    with knocker:
+        time.sleep(0.3)
+
+    assert len(points) > 1
+    assert all(point["name"] == "gilknocker.contention_metric" for point in points)
+    assert all(0.0 <= point["value"] <= 1.0 for point in points)
+    timestamps = [point["time_unix_nano"] for point in points]
+    assert timestamps == sorted(timestamps)
+    assert timestamps[0] >= before - 1_000_000_000
+
+    # exceptions are warned about, rather than stopping the monitoring thread
+    knocker.otel_observe(lambda point: 1 / 0)
+    with pytest.warns(UserWarning, match="Exception in callback"):
+        with knocker:
+            time.sleep(0.1)
+    knocker.otel_observe(None)
+
+
+def test_knockknock_stop_nowait():
+    async def main():
+        knocker = KnockKnock(polling_interval_micros=1000)
+        knocker.start()
+        await asyncio.sleep(0.1)
+
+        start = time.monotonic()
+        knocker.stop_nowait()
+        assert time.monotonic() - start < 0.01
+
+        assert await knocker.await_stopped()
+        assert not knocker.is_running
+        assert knocker.stop() == knocker.contention_metric
+
+        # nothing to wait for once stopped
+        assert await knocker.await_stopped()
+
+    asyncio.run(main())
+
+
+def test_knockknock_polls_per_window():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.polls_per_window == 0
+    assert knocker.avg_polls_per_window == 0.0
+    with knocker:
+        time.sleep(0.5)
+
+    # ~10 polls of 1ms in a 10ms window, fewer if the OS oversleeps, but not far off
+    assert 3 <= knocker.avg_polls_per_window <= 11
+    assert 1 <= knocker.polls_per_window <= 11
+
+    knocker.reset_contention_metric()
+    assert knocker.polls_per_window == 0
+    assert knocker.avg_polls_per_window == 0.0
+
+
+def test_knockknock_adaptive_polling():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=20_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert not knocker.adaptive
+    assert knocker.effective_polling_interval_micros == 1000
+    knocker.set_adaptive_polling(
+        True, min_polling_micros=100, max_polling_micros=10_000
+    )
+    assert knocker.adaptive
+
+    knocker.start()
+    try:
+        time.sleep(0.3)
+        quiet = knocker.effective_polling_interval_micros
+        assert quiet > 5000
+
+        with contention():
+            time.sleep(0.5)
+            contended = knocker.effective_polling_interval_micros
+        assert contended < quiet
+
+        time.sleep(0.3)
+        assert knocker.effective_polling_interval_micros > contended
+
+        knocker.set_adaptive_polling(False)
+        time.sleep(0.05)
+        assert knocker.effective_polling_interval_micros == 1000
+    finally:
+        knocker.stop()
+
+    with pytest.raises(ValueError):
+        knocker.set_adaptive_polling(True, min_polling_micros=10, max_polling_micros=1)
+
+
+def test_knockknock_min_valid_samples():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=50_000,
+    )
+    assert knocker.min_valid_samples == 0
+    assert knocker.is_metric_valid
+
+    knocker.min_valid_samples = 5
+    assert not knocker.is_metric_valid
+    knocker.start()
+    try:
+        while knocker.num_samples < 5:
+            assert not knocker.is_metric_valid or knocker.num_samples >= 5
+            time.sleep(0.005)
+        assert knocker.is_metric_valid
+
+        knocker.reset_contention_metric()
+        assert not knocker.is_metric_valid
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_wait_for_sample():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=50_000,
+    )
+    # Nothing to wait for before starting
+    assert not knocker.wait_for_sample(0.1)
+
+    knocker.start()
+    try:
+        num_samples = knocker.num_samples
+        start = time.perf_counter()
+        # Generous bound on sampling + sleeping intervals, for slow CI
+        assert knocker.wait_for_sample(1.0)
+        assert time.perf_counter() - start < 1.0
+        assert knocker.num_samples > num_samples
+    finally:
+        knocker.stop()
+
+    with pytest.raises(ValueError):
+        knocker.wait_for_sample(-1.0)
+
+
+def test_knockknock_reset_returns_whether_acknowledged():
+    # Not running, so the reset is applied directly
+    assert KnockKnock(1_000).reset_contention_metric()
+
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=100_000,
+        sleeping_interval_micros=100_000,
+    )
+    knocker.start()
+    try:
+        with contention():
+            knocker.wait_for_sample(1.0)
+            assert knocker.num_samples > 0
+            assert knocker.reset_contention_metric()
+            assert knocker.num_samples == 0
+            assert knocker.contention_metric == 0.0
+    finally:
+        knocker.stop()
+
+    # Polling only every second, the thread can't acknowledge within the timeout
+    with pytest.warns(UserWarning, match="less than polling_interval_micros"):
+        knocker = KnockKnock(
+            polling_interval_micros=1_000_000,
+            sampling_interval_micros=10_000_000,
+            timeout_micros=1_000,
+        )
+    knocker.start()
+    try:
+        time.sleep(0.1)
+        with pytest.warns(UserWarning):
+            assert not knocker.reset_contention_metric()
+    finally:
+        with warnings.catch_warnings():
+            warnings.simplefilter("ignore")
+            knocker.stop()
+
+
+def test_knockknock_set_timeout_micros():
+    # Polling only every second, the thread is stuck sleeping when asked to stop
+    knocker = KnockKnock(
+        polling_interval_micros=1_000_000,
+        sampling_interval_micros=10_000_000,
+    )
+    assert knocker.timeout_micros == 10_001_000
+    with pytest.warns(UserWarning, match="less than polling_interval_micros"):
+        knocker.set_timeout_micros(50_000)
+    assert knocker.timeout_micros == 50_000
+
+    with pytest.raises(ValueError):
+        knocker.set_timeout_micros(0)
+    assert knocker.timeout_micros == 50_000
+
+    knocker.start()
+    time.sleep(0.1)
+    start = time.perf_counter()
+    with pytest.warns(UserWarning, match="Timed out waiting for monitoring thread"):
+        knocker.stop()
+    assert time.perf_counter() - start < 1.0
+
+
+def test_knockknock_compare():
+    knocker = _run(a_lotta_gil)
+    idle = KnockKnock(1_000)
+    try:
+        assert knocker > -1.0
+        assert knocker >= 0.0
+        assert knocker <= 1.0
+        assert not knocker < 0.0
+        assert knocker > idle
+        assert idle < knocker
+        assert idle <= 0
+    finally:
+        knocker.stop()
+
+    # Equality is by identity, not metric
+    assert idle == idle
+    assert idle != KnockKnock(1_000)
+    assert {idle, knocker} == {knocker, idle}
+    with pytest.raises(TypeError):
+        knocker < "0.5"
+
+
+def test_knockknock_ema_weights_by_sampling_time(tmp_path):
+    alpha = 0.5
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=5_000,
+        sleeping_interval_micros=5_000,
+        ema_alpha=alpha,
+    )
+    knocker.start()
+    try:
+        # Short idle windows, then long contended ones, then short idle ones again
+        for _ in range(3):
+            knocker.wait_for_sample(1.0)
+        knocker.set_sampling_interval_micros(50_000)
+        with contention():
+            for _ in range(4):
+                knocker.wait_for_sample(1.0)
+        knocker.set_sampling_interval_micros(5_000)
+        for _ in range(3):
+            knocker.wait_for_sample(1.0)
+    finally:
+        knocker.stop()
+
+    path = tmp_path / "history.csv"
+    knocker.write_csv(path)
+    rows = np.loadtxt(path, delimiter=",", skiprows=1, ndmin=2)
+    waiting = sampling = naive = 0.0
+    for _, ratio, time_waiting, time_sampling in rows:
+        waiting = alpha * time_waiting + (1 - alpha) * waiting
+        sampling = alpha * time_sampling + (1 - alpha) * sampling
+        naive = alpha * ratio + (1 - alpha) * naive
+    weighted = min(max(waiting / sampling, 0.0), 1.0)
+
+    assert knocker.contention_metric == pytest.approx(weighted)
+    # The long contended windows outweigh the short idle ones which followed them
+    assert weighted > naive
+
+
+def test_mergeable_stats():
+    first = MergeableStats(100, 1_000, 2)
+    second = MergeableStats(900, 3_000, 6)
+    merged = first.merge(second)
+    assert merged.total_time_waiting_micros == 1_000
+    assert merged.total_time_sampling_micros == 4_000
+    assert merged.num_samples == 8
+    # Pooled, rather than the mean of each ratio
+    assert merged.contention_metric == pytest.approx(1_000 / 4_000)
+    assert merged.contention_metric != pytest.approx(
+        (first.contention_metric + second.contention_metric) / 2
+    )
+    assert MergeableStats().contention_metric == 0.0
+
+    roundtripped = MergeableStats.from_bytes(merged.to_bytes())
+    assert roundtripped.total_time_waiting_micros == 1_000
+    assert roundtripped.total_time_sampling_micros == 4_000
+    assert roundtripped.num_samples == 8
+    with pytest.raises(ValueError):
+        MergeableStats.from_bytes(b"not stats")
+
+
+def test_mergeable_stats_contention_metric_bounds():
+    # No sampling time is no contention, rather than NaN or an error
+    assert MergeableStats(500, 0, 1).contention_metric == 0.0
+    # Waits slightly exceeding the time sampled clamp to full contention
+    assert MergeableStats(1_001, 1_000, 1).contention_metric == 1.0
+
+
+def test_knockknock_mergeable_stats():
+    knocker = _run(a_lotta_gil)
+    knocker.stop()
+    stats = knocker.mergeable_stats()
+    snapshot = knocker.snapshot()
+    assert stats.num_samples == snapshot.num_samples
+    assert stats.total_time_waiting_micros == snapshot.total_time_waiting_micros
+    assert stats.total_time_sampling_micros == snapshot.total_time_sampling_micros
+    assert stats.contention_metric == pytest.approx(knocker.contention_metric, abs=1e-3)
+
+
+def test_knockknock_healthy():
+    blocked, unblock = threading.Event(), threading.Event()
+
+    def block(*_):
+        blocked.set()
+        unblock.wait(5)
+
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.healthy
+    knocker.start()
+    try:
+        assert knocker.healthy
+
+        # Block the monitoring thread in a callback after its first sample, then zero
+        # the metrics without it, so it's left monitoring without completing a sample.
+        knocker.set_sample_callback(block)
+        assert blocked.wait(1.0)
+        knocker.set_timeout_micros(10_000)
+        with pytest.warns(UserWarning):
+            assert not knocker.reset_contention_metric()
+        assert knocker.num_samples == 0
+        time.sleep(0.2)
+        assert not knocker.healthy
+    finally:
+        knocker.set_sample_callback(None)
+        unblock.set()
+        knocker.set_timeout_micros(1_000_000)
+        knocker.stop()
+
+
+def test_knockknock_round_digits():
+    knocker = _run(a_lotta_gil)
+    knocker.stop()
+    assert knocker.round_digits is None
+    unrounded = knocker.contention_metric
+    unrounded_pct = knocker.contention_metric_pct
+
+    knocker.round_digits = 3
+    assert knocker.round_digits == 3
+    assert knocker.contention_metric == round(unrounded, 3)
+    assert knocker.contention_metric_pct == round(unrounded_pct, 3)
+    for value in (knocker.contention_metric, knocker.contention_metric_pct):
+        assert len(repr(value).partition(".")[2]) <= 3
+
+    # The accumulated metric isn't affected
+    knocker.round_digits = None
+    assert knocker.contention_metric == unrounded
+
+
+def test_knockknock_window_peak():
+    knocker = _run(a_lotta_gil)
+    try:
+        knocker.pause()
+        peak = max(knocker.history())
+        assert knocker.window_peak(10.0) > 0.5
+        assert knocker.window_peak(10.0) >= peak
+        assert knocker.window_peak(0.0) == 0.0
+
+        # Idle past the window, without sampling
+        time.sleep(0.3)
+        assert knocker.window_peak(0.2) == 0.0
+        assert knocker.window_peak(10.0) > 0.5
+    finally:
+        knocker.stop()
+
+    with pytest.raises(ValueError):
+        knocker.window_peak(-1.0)
+
+
+def test_knockknock_lifecycle_callbacks():
+    events = []
+    knocker = KnockKnock(1_000)
+    knocker.set_lifecycle_callbacks(
+        lambda: events.append(("start", knocker.is_running)),
+        lambda: events.append("stop"),
+    )
+    knocker.start()
+    assert events == [("start", True)]
+    knocker.stop()
+    assert events == [("start", True), "stop"]
+    # Already stopped, so nothing to join
+    knocker.stop()
+    assert events == [("start", True), "stop"]
+
+    def boom():
+        raise ValueError("boom")
+
+    knocker.set_lifecycle_callbacks(boom, boom)
+    with pytest.warns(UserWarning, match="boom"):
+        knocker.start()
+    with pytest.warns(UserWarning, match="boom"):
+        knocker.stop()
+
+    knocker.set_lifecycle_callbacks()
+    knocker.start()
+    knocker.stop()
+    assert events == [("start", True), "stop"]
+
+
+def test_knockknock_config_dict_roundtrip():
+    knocker = KnockKnock(1_000, 20_000, 300_000, 400_000)
+    config = knocker.config_dict()
+    assert config == {
+        "polling_interval_micros": 1_000,
+        "sampling_interval_micros": 20_000,
+        "sleeping_interval_micros": 300_000,
+        "timeout_micros": 400_000,
+    }
+    assert KnockKnock.from_dict(config).config_dict() == config
+
+    # Missing keys get the constructor's defaults
+    partial = KnockKnock.from_dict({"polling_interval_micros": 2_000})
+    assert partial.config_dict() == KnockKnock(2_000).config_dict()
+    assert KnockKnock.from_dict({}).config_dict() == KnockKnock().config_dict()
+
+    with pytest.raises(ValueError, match="polling_micros"):
+        KnockKnock.from_dict({"polling_micros": 1_000})
+
+
+def test_knockknock_spin():
+    def avg_polls(spin):
+        knocker = KnockKnock(
+            polling_interval_micros=100,
+            sampling_interval_micros=20_000,
+            sleeping_interval_micros=1_000,
+        )
+        knocker.spin = spin
+        assert knocker.spin is spin
+        knocker.start()
+        try:
+            for _ in range(10):
+                knocker.wait_for_sample(1.0)
+        finally:
+            knocker.stop()
+        return knocker.avg_polls_per_window
+
+    # Target is 200 polls per window, which sleeping falls short of
+    assert KnockKnock().spin is False
+    assert avg_polls(True) > avg_polls(False)
+
+
+def test_knockknock_interval_getters():
+    knocker = KnockKnock(1_000, 20_000, 300_000, 400_000)
+    assert knocker.polling_interval_micros == 1_000
+    assert knocker.sampling_interval_micros == 20_000
+    assert knocker.sleeping_interval_micros == 300_000
+    assert knocker.timeout_micros == 400_000
+
+    # Defaults derived from the polling interval
+    knocker = KnockKnock(polling_interval_micros=500)
+    assert knocker.sampling_interval_micros == 5_000
+    assert knocker.sleeping_interval_micros == 50_000
+    assert knocker.timeout_micros == 51_000
+
+
+def test_knockknock_latency_percentiles():
+    knocker = KnockKnock(1_000)
+    assert knocker.latency_percentiles() == [0.0, 0.0, 0.0]
+
+    knocker = _run(a_lotta_gil)
+    knocker.stop()
+    p50, p95, p99 = knocker.latency_percentiles([0.5, 0.95, 0.99])
+    assert 0 < p50 <= p95 <= p99
+    assert knocker.latency_percentiles() == [p50, p95, p99]
+    assert knocker.latency_percentiles([]) == []
+
+    with pytest.raises(ValueError):
+        knocker.latency_percentiles([1.5])
+
+
+def test_knockknock_overrun():
+    overruns = []
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=5_000,
+        sleeping_interval_micros=5_000,
+    )
+    knocker.set_overrun_callback(overruns.append)
+    knocker.start()
+    try:
+        assert knocker.wait_for_sample(1.0)
+        # Hold the GIL in C, without any bytecode to switch threads at, for much
+        # longer than a window's 10ms budget
+        for _ in range(3):
+            sum(range(10_000_000))
+        assert knocker.wait_for_sample(1.0)
+    finally:
+        knocker.stop()
+
+    assert knocker.overrun_count >= 1
+    assert len(overruns) == knocker.overrun_count
+    assert all(overrun > 0 for overrun in overruns)
+
+    knocker.reset_contention_metric()
+    assert knocker.overrun_count == 0
+
+
+def test_knockknock_single_probe():
+    def measure(busy_threads):
+        knocker = KnockKnock(
+            polling_interval_micros=1_000,
+            sampling_interval_micros=10_000,
+            sleeping_interval_micros=10_000,
+        )
+        knocker.single_probe = True
+        assert knocker.single_probe
+        knocker.start()
+        try:
+            with contention(busy_threads):
+                for _ in range(20):
+                    knocker.wait_for_sample(1.0)
+        finally:
+            knocker.stop()
+        # A single acquisition per window
+        assert knocker.avg_polls_per_window <= 1.0
+        return knocker.contention_metric
+
+    assert KnockKnock().single_probe is False
+    idle, busy = measure(0), measure(N_THREADS)
+    assert idle < 0.1
+    assert busy > idle
+
+
+def test_default_knocker():
+    def first_call_site():
+        return gilknocker.start_default()
+
+    def second_call_site():
+        knocker = gilknocker.start_default()
+        knocker.wait_for_sample(1.0)
+        return knocker.contention_metric
+
+    knocker = first_call_site()
+    try:
+        assert knocker is gilknocker.default()
+        assert knocker.is_running
+        assert 0.0 <= second_call_site() <= 1.0
+        assert knocker.num_samples > 0
+    finally:
+        metric = gilknocker.stop_default()
+    assert metric == knocker.contention_metric
+    assert not gilknocker.default().is_running
+
+
+@pytest.mark.skipif(sys.platform.startswith("win"), reason="Requires Unix")
+def test_knockknock_stream_to_fd():
+    read_fd, write_fd = os.pipe()
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=5_000,
+        sleeping_interval_micros=5_000,
+    )
+    knocker.stream_to_fd(write_fd, every=2)
+    knocker.start()
+    try:
+        for _ in range(6):
+            knocker.wait_for_sample(1.0)
+    finally:
+        knocker.stop()
+        os.close(write_fd)
+    with os.fdopen(read_fd) as pipe:
+        lines = pipe.read().splitlines()
+
+    assert len(lines) == knocker.num_samples // 2
+    for line in lines:
+        record = json.loads(line)
+        assert set(record) == {"t", "ratio", "metric"}
+        assert 0.0 <= record["ratio"] <= 1.0
+        assert 0.0 <= record["metric"] <= 1.0
+        assert abs(record["t"] - time.time()) < 60
+
+    with pytest.raises(ValueError):
+        knocker.stream_to_fd(write_fd, every=0)
+    with pytest.raises(ValueError):
+        knocker.stream_to_fd(-1)
+
+
+@pytest.mark.skipif(sys.platform.startswith("win"), reason="Requires Unix")
+def test_knockknock_stream_to_fd_error_disables():
+    read_fd, write_fd = os.pipe()
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=5_000,
+        sleeping_interval_micros=5_000,
+    )
+    # Writing to the read end of a pipe fails
+    knocker.stream_to_fd(read_fd)
+    try:
+        with pytest.warns(UserWarning, match="streaming disabled"):
+            knocker.start()
+            for _ in range(3):
+                knocker.wait_for_sample(1.0)
+            knocker.stop()
+    finally:
+        os.close(read_fd)
+        os.close(write_fd)
+
+
+def test_knockknock_heartbeat():
+    beats = []
+    # Sleeping between samples for longer than the heartbeat interval
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=300_000,
+    )
+    knocker.set_heartbeat(0.1, beats.append)
+    knocker.start()
+    try:
+        time.sleep(1.0)
+    finally:
+        knocker.stop()
+
+    # ~10 expected over 1s, allowing for slow CI
+    assert 5 <= len(beats) <= 11
+    assert all(0.0 <= metric <= 1.0 for metric in beats)
+
+    with pytest.raises(ValueError):
+        knocker.set_heartbeat(0.0, beats.append)
+
+
+def test_knockknock_estimated_overhead_pct():
+    def overhead(*intervals):
+        knocker = KnockKnock(*intervals)
+        assert knocker.estimated_overhead_pct is None
+        knocker.start()
+        knocker.wait_for_sample(2.0)
+        knocker.stop()
+        return knocker.estimated_overhead_pct
+
+    high = overhead(100, 100_000, 1_000)
+    low = overhead(10_000, 20_000, 1_000_000)
+    assert 0.0 < low < high <= 100.0
+
+
+def test_knockknock_estimated_overhead_pct_none_until_polled():
+    knocker = KnockKnock(polling_interval_micros=1_000)
+    assert knocker.estimated_overhead_pct is None
+    knocker.start()
+    try:
+        assert knocker.wait_for_sample(2.0)
+    finally:
+        knocker.stop()
+    assert knocker.estimated_overhead_pct is not None
+
+    # No calibration to fall back on once the polls are reset
+    knocker.reset_contention_metric()
+    assert knocker.estimated_overhead_pct is None
+
+
+def test_knockknock_read_and_reset():
+    knocker = KnockKnock(
+        polling_interval_micros=1_000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=20_000,
+    )
+    knocker.start()
+    try:
+        with contention():
+            time.sleep(0.2)
+            knocker.read_and_reset()  # discard warming up
+
+            readings = []
+            for _ in range(5):
+                time.sleep(0.3)
+                readings.append(knocker.read_and_reset())
+    finally:
+        knocker.stop()
+
+    # Each interval is measured independently, under the same steady contention
+    assert all(reading > 0.3 for reading in readings)
+    assert max(readings) - min(readings) < 0.3
+
+
+@pytest.mark.skipif(not sys.platform.startswith("linux"), reason="Requires /proc")
+def test_knockknock_monitor_thread_id():
+    knocker = KnockKnock(1_000)
+    knocker.thread_name_prefix = "knocktid"
+    assert knocker.monitor_thread_id is None
+    knocker.start()
+    try:
+        thread_id = knocker.monitor_thread_id
+        assert str(thread_id) in os.listdir("/proc/self/task")
+        with open(f"/proc/self/task/{thread_id}/comm") as comm:
+            assert comm.read().strip() == "knocktid-monitor"[:15]
+        assert thread_id != os.getpid()  # the main thread's id
+    finally:
+        knocker.stop()
+    assert knocker.monitor_thread_id is None
+
+
+def test_knockknock_short_timeout_with_long_sleep():
+    # Messages wake the thread while sleeping, so a timeout far shorter than the
+    # sleeping interval is fine, without warning
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        knocker = KnockKnock(
+            polling_interval_micros=1_000,
+            sampling_interval_micros=5_000,
+            sleeping_interval_micros=10_000_000,
+            timeout_micros=200_000,
+        )
+    knocker.start()
+    try:
+        assert knocker.wait_for_sample(1.0)  # now sleeping
+        for _ in range(3):
+            assert knocker.reset_contention_metric()
+    finally:
+        start = time.perf_counter()
+        knocker.stop()
+    assert time.perf_counter() - start < 1.0
+
+
+def test_knockknock_measure_call():
+    def hold_gil(n):
+        total = 0
+        end = time.perf_counter() + 0.3
+        while time.perf_counter() < end:
+            total += n
+        return total
+
+    def sleep(secs):
+        time.sleep(secs)
+        return "slept"
+
+    knocker = KnockKnock(polling_interval_micros=1_000)
+    knocker.start()  # measuring works alongside a running knocker
+    try:
+        result, busy = knocker.measure_call(hold_gil, 1)
+        assert result > 0
+        result, idle = knocker.measure_call(sleep, secs=0.3)
+        assert result == "slept"
+        assert knocker.is_running
+    finally:
+        knocker.stop()
+    assert busy > idle
+
+    with pytest.raises(ZeroDivisionError):
+        knocker.measure_call(lambda: 1 / 0)
+
+
+def test_knockknock_stream():
+    knocker = KnockKnock(
+        polling_interval_micros=100,
+        sampling_interval_micros=1_000,
+        sleeping_interval_micros=1_000,
+    )
+
+    async def main():
+        stream = knocker.stream(maxsize=4)
+        knocker.start()
+        samples = []
+        try:
+            async for timestamp, metric in stream:
+                assert timestamp > 0
+                assert 0 <= metric <= 1
+                samples.append(timestamp)
+                if len(samples) == 5:
+                    break
+        finally:
+            stream.close()
+            knocker.stop()
+        assert stream.closed
+        assert samples == sorted(samples)
+
+        # Ends once the monitoring thread exits
+        stream = knocker.stream()
+        knocker.start()
+        knocker.stop()
+        async for _ in stream:
+            pass
+        assert stream.closed
+
+    asyncio.run(main())
+
+    with pytest.raises(ValueError):
+        knocker.stream(maxsize=0)
+
+
+def test_knockknock_stream_when_not_running():
+    knocker = KnockKnock(polling_interval_micros=1000)
+
+    async def consume():
+        return [sample async for sample in knocker.stream()]
+
+    # Never started, then stopped
+    assert knocker.stream().closed
+    assert asyncio.run(asyncio.wait_for(consume(), 5)) == []
+    knocker.start()
+    knocker.stop()
+    assert knocker.stream().closed
+    assert asyncio.run(asyncio.wait_for(consume(), 5)) == []
+
+
+def test_knockknock_adaptive_polling_bounds():
+    knocker = KnockKnock(
+        polling_interval_micros=2000,
+        sampling_interval_micros=20_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.adaptive_min_micros is None
+    assert knocker.adaptive_max_micros is None
+
+    # Derived from the polling interval by default
+    knocker.set_adaptive_polling(True)
+    assert knocker.adaptive_min_micros == 200
+    assert knocker.adaptive_max_micros == 20_000
+
+    knocker.set_adaptive_polling(
+        True, min_polling_micros=500, max_polling_micros=5_000
+    )
+    assert knocker.adaptive_min_micros == 500
+    assert knocker.adaptive_max_micros == 5_000
+
+    knocker.start()
+    try:
+        with contention():
+            for _ in range(20):
+                time.sleep(0.02)
+                assert 500 <= knocker.effective_polling_interval_micros <= 5_000
+    finally:
+        knocker.stop()
+
+    for min_micros, max_micros in [(0, 10), (10, 0), (10, 1)]:
+        with pytest.raises(ValueError):
+            knocker.set_adaptive_polling(
+                True, min_polling_micros=min_micros, max_polling_micros=max_micros
+            )
+
+
+def test_knockknock_is_sampling():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=50_000,
+        sleeping_interval_micros=50_000,
+    )
+    assert not knocker.is_sampling
+
+    observed = set()
+    knocker.start()
+    try:
+        deadline = time.monotonic() + 1
+        while time.monotonic() < deadline and len(observed) < 2:
+            observed.add(knocker.is_sampling)
+            time.sleep(0.001)
+    finally:
+        knocker.stop()
+    assert observed == {True, False}
+    assert not knocker.is_sampling
+
+
+def test_knockknock_final_callback():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    summaries = []
+    knocker.set_final_callback(summaries.append)
+
+    knocker.start()
+    time.sleep(0.1)
+    assert not summaries
+    metric = knocker.stop()
+
+    assert len(summaries) == 1
+    summary = summaries[0]
+    assert summary["contention_metric"] == metric == knocker.contention_metric
+    assert summary["peak_contention_metric"] == knocker.peak_contention_metric
+    assert summary["num_samples"] == knocker.num_samples > 0
+    assert summary["elapsed_secs"] == knocker.elapsed_secs
+
+    # Once per stop
+    knocker.stop()
+    assert len(summaries) == 1
+
+    knocker.set_final_callback(None)
+    knocker.start()
+    knocker.stop()
+    assert len(summaries) == 1
+
+
+def test_knockknock_sample_rate():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.sample_rate == 0.0
+
+    knocker.start()
+    try:
+        time.sleep(0.5)
+        # At most one sample per 20ms, allowing for scheduling delays
+        assert 10 < knocker.sample_rate <= 55
+
+        knocker.reset_contention_metric()
+        assert knocker.sample_rate == 0.0
+    finally:
+        knocker.stop()
+
+
+def test_knockknock_reset_while_sleeping_is_prompt():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=5_000_000,
+        timeout_micros=10_000_000,
+    )
+    knocker.start()
+    try:
+        # Well into the first sleep between sampling windows
+        assert knocker.wait_for_sample(1.0)
+        time.sleep(0.1)
+
+        start = time.monotonic()
+        assert knocker.reset_contention_metric()
+        assert time.monotonic() - start < 0.5
+        assert knocker.num_samples == 0
+    finally:
+        start = time.monotonic()
+        knocker.stop()
+        assert time.monotonic() - start < 0.5
+
+
+def test_knockknock_dump_json():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=5_000,
+        sleeping_interval_micros=5_000,
+        history_size=8,
+    )
+    knocker.start()
+    try:
+        time.sleep(0.2)
+        dump = json.loads(knocker.dump_json(include_history=True))
+    finally:
+        knocker.stop()
+
+    assert dump["is_running"]
+    assert dump["history_size"] == 8
+    assert dump["num_samples"] > 8
+    assert len(dump["history"]) == min(dump["num_samples"], 8)
+    timestamps = [sample["t"] for sample in dump["history"]]
+    assert timestamps == sorted(timestamps)
+    for sample in dump["history"]:
+        assert 0 <= sample["ratio"] <= 1
+        assert sample["time_waiting_micros"] <= sample["time_sampling_micros"]
+
+    dump = json.loads(knocker.dump_json())
+    assert "history" not in dump
+    assert not dump["is_running"]
+    assert dump["num_samples"] == knocker.num_samples
+    assert dump["contention_metric"] == knocker.contention_metric
+    assert dump["polling_interval_micros"] == 1000
+
+
+def test_knockknock_rapid_start_stop():
+    with contention(1):
+        for _ in range(50):
+            knocker = KnockKnock(
+                polling_interval_micros=100,
+                sampling_interval_micros=1_000,
+                sleeping_interval_micros=1_000,
+            )
+            knocker.start()
+            knocker.stop()
+            knocker.start()
+            del knocker
+
+
+def test_knockknock_exit_while_running():
+    # Interpreter teardown with knockers still running, or dropped mid-sample
+    code = """
+import gilknocker
+knockers = [gilknocker.KnockKnock(100, 1_000, 1_000) for _ in range(10)]
+for knocker in knockers:
+    knocker.start()
+knockers[0].stop()
+del knockers[1]
+"""
+    for _ in range(5):
+        result = subprocess.run([sys.executable, "-c", code], timeout=60)
+        assert result.returncode == 0
+
+
+def test_knockknock_measuring():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=1_000,
+    )
+    knocker.start()
+    try:
+        for _ in range(2):
+            with knocker.measuring() as busy:
+                assert busy.contention_metric is None
+                with contention():
+                    time.sleep(0.3)
+
+            with knocker.measuring() as idle:
+                time.sleep(0.3)
+
+            assert knocker.is_running
+            assert idle.contention_metric < busy.contention_metric
+            assert idle.contention_metric < 0.1
+    finally:
+        knocker.stop()
+
+    # Started and stopped around the block when not running
+    with knocker.measuring() as m:
+        assert knocker.is_running
+        time.sleep(0.05)
+    assert not knocker.is_running
+    assert m.contention_metric == knocker.contention_metric
+
+
+def test_knockknock_total_secs():
+    knocker = KnockKnock(
+        polling_interval_micros=1000,
+        sampling_interval_micros=10_000,
+        sleeping_interval_micros=10_000,
+    )
+    assert knocker.total_wait_secs == knocker.total_monitored_secs == 0.0
+
+    knocker.start()
+    try:
+        with contention():
+            time.sleep(0.3)
+    finally:
+        knocker.stop()
+
+    assert 0 < knocker.total_wait_secs <= knocker.total_monitored_secs
+    assert knocker.total_wait_secs / knocker.total_monitored_secs == pytest.approx(
+        knocker.contention_metric, abs=1e-3
+    )
+    assert knocker.total_monitored_secs == pytest.approx(
+        knocker.total_time_sampling_micros / 1e6, abs=1e-6
+    )
+
+    knocker.reset_contention_metric()
+    assert knocker.total_wait_secs == knocker.total_monitored_secs == 0.0
+
+
+def test_knockknock_monitor_panic():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.start()
+    knocker._panic_monitor()
+    deadline = time.monotonic() + 5
+    while knocker.is_running and time.monotonic() < deadline:
+        time.sleep(0.01)
+
+    # Recorded as soon as the thread panics, before it's joined
+    assert not knocker.is_running
+    assert knocker.error == "Forced panic"
+
+    # Raised by the next start, rather than lost
+    with pytest.raises(RuntimeError, match="Forced panic"):
+        knocker.start()
+
+    knocker.start()
+    assert knocker.error is None
+    knocker.stop()
+
+@pytest.mark.skipif(
+    not hasattr(atexit, "_ncallbacks"), reason="Requires CPython's atexit"
+)
+def test_knockknock_start_registers_one_atexit_hook():
+    knocker = KnockKnock(polling_interval_micros=1000)
+    knocker.start()
+    knocker.stop()
+    registered = atexit._ncallbacks()
+
+    for _ in range(5):
+        knocker.start()
+        knocker.stop()
+        other = KnockKnock(polling_interval_micros=1000)
+        other.start()
+        other.stop()
+    assert atexit._ncallbacks() == registered
+
+
+def test_knockknock_usable_from_other_threads_while_stopping():
+    in_callback = threading.Event()
+
+    def slow_callback(*_sample):
+        in_callback.set()
+        time.sleep(0.5)
+
+    knocker = KnockKnock(polling_interval_micros=100, timeout_micros=5_000_000)
+    knocker.set_sample_callback(slow_callback)
+    knocker.start()
+    assert in_callback.wait(5)
+
+    # stop() waits for the monitoring thread, stuck in the callback, without keeping
+    # the knocker borrowed, so other threads can still use it meanwhile
+    stopper = threading.Thread(target=knocker.stop)
+    stopper.start()
+    time.sleep(0.1)
+    assert stopper.is_alive()
+    assert 0.0 <= knocker.contention_metric <= 1.0
+    assert knocker.reset_contention_metric()
+    stopper.join()
+    assert not knocker.is_running
+
+
 # Manual verification with py-spy
 # busy should give high GIL %
 if __name__ == "__main__":
@@ -0,0 +1,39 @@
+pytest_plugins = ["pytester"]
+
+
+def test_gil_contention_fixture(pytester):
+    pytester.makepyfile(
+        """
+        def test_first(gil_contention):
+            assert gil_contention.is_running
+            assert 0.0 <= gil_contention.contention_metric <= 1.0
+
+        def test_second(gil_contention):
+            assert gil_contention.is_running
+        """
+    )
+    result = pytester.runpytest()
+    result.assert_outcomes(passed=2)
+
+
+def test_gil_threshold_option(pytester):
+    pytester.makepyfile(
+        """
+        import time
+
+        def test_sleepy(gil_contention):
+            time.sleep(0.5)
+
+        def test_busy(gil_contention):
+            end = time.time() + 1
+            while time.time() < end:
+                pass
+        """
+    )
+    result = pytester.runpytest("--gil-threshold=0.1")
+    result.assert_outcomes(passed=1, failed=1)
+    result.stdout.fnmatch_lines(["*exceeded threshold of 0.1*"])
+
+    # no threshold, no failures
+    result = pytester.runpytest()
+    result.assert_outcomes(passed=2)